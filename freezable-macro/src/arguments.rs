@@ -0,0 +1,135 @@
+//! the arguments of `#[freezable(...)]`, all of them are optional
+//!
+//! ```ignore
+//! #[freezable(name = "Countdown", derive(Debug, Clone, PartialEq), state_prefix = "Step", start = "new")]
+//! fn countdown(from: u8) -> u8 {
+//!     ...
+//! }
+//!
+//! let mut countdown = Countdown::new(3); // `Countdown::Step0(3)`
+//! ```
+//!
+//! - `name`: the name of the generated type, instead of the name of the function
+//! - `derive`: the traits that are derived for the generated enum
+//! - `state_prefix`: the variants of the chunks are named `{state_prefix}0`, `{state_prefix}1`, ... (`Chunk` by default)
+//! - `start`: the name of the function that creates the state machine (`start` by default)
+//! - `crate`: the path of the `freezable` crate in the generated code (`::freezable` by default),
+//!   for the crates that re-export it -> `crate = "my_crate::freezable"`
+
+use crate::generator::GeneratorTypes;
+use quote::format_ident;
+use syn::{parse_quote, spanned::Spanned, Ident, Lit, Meta, NestedMeta};
+
+pub struct Arguments {
+    pub name: Option<Ident>,
+    pub derives: Vec<syn::Path>,
+    pub state_prefix: String,
+    pub start: Ident,
+    pub krate: syn::Path,
+    /// not an argument, it is set by `#[generator]`
+    pub generator: Option<GeneratorTypes>,
+}
+
+impl Default for Arguments {
+    fn default() -> Self {
+        Arguments {
+            name: None,
+            derives: vec![],
+            state_prefix: "Chunk".to_string(),
+            start: format_ident!("start"),
+            krate: parse_quote!(::freezable),
+            generator: None,
+        }
+    }
+}
+
+impl Arguments {
+    pub fn parse(args: Vec<NestedMeta>) -> Result<Self, syn::Error> {
+        let mut arguments = Arguments::default();
+        let mut seen: Vec<String> = vec![];
+        for arg in args {
+            let key = match &arg {
+                NestedMeta::Meta(meta) => meta.path().get_ident().map(|ident| ident.to_string()),
+                NestedMeta::Lit(_) => None,
+            };
+            if let Some(key) = &key {
+                if seen.contains(key) {
+                    return Err(syn::Error::new(
+                        arg.span(),
+                        format!("`{}` is given more than once", key),
+                    ));
+                }
+                seen.push(key.clone());
+            }
+
+            match (key.as_deref(), &arg) {
+                (Some("name"), NestedMeta::Meta(Meta::NameValue(name))) => {
+                    arguments.name = Some(parse_ident(&name.lit)?);
+                }
+                (Some("start"), NestedMeta::Meta(Meta::NameValue(start))) => {
+                    arguments.start = parse_ident(&start.lit)?;
+                }
+                (Some("crate"), NestedMeta::Meta(Meta::NameValue(krate))) => {
+                    let Lit::Str(text) = &krate.lit else {
+                        return Err(syn::Error::new(krate.lit.span(), "expected a path in a string"));
+                    };
+                    arguments.krate = text.parse()?;
+                }
+                (Some("state_prefix"), NestedMeta::Meta(Meta::NameValue(prefix))) => {
+                    let Lit::Str(text) = &prefix.lit else {
+                        return Err(syn::Error::new(prefix.lit.span(), "expected a string"));
+                    };
+                    // the first variant should be a valid name
+                    if syn::parse_str::<Ident>(&format!("{}0", text.value())).is_err() {
+                        return Err(syn::Error::new(
+                            text.span(),
+                            "the prefix of the variants should be the beginning of a name, like `Step`",
+                        ));
+                    }
+                    arguments.state_prefix = text.value();
+                }
+                (Some("derive"), NestedMeta::Meta(Meta::List(list))) => {
+                    for derive in &list.nested {
+                        match derive {
+                            NestedMeta::Meta(Meta::Path(path)) => {
+                                arguments.derives.push(path.clone())
+                            }
+                            other => {
+                                return Err(syn::Error::new(
+                                    other.span(),
+                                    "expected the name of a trait",
+                                ))
+                            }
+                        }
+                    }
+                }
+                (Some("name" | "start" | "state_prefix" | "crate"), _) => {
+                    return Err(syn::Error::new(
+                        arg.span(),
+                        format!("expected `{} = \"...\"`", key.unwrap()),
+                    ))
+                }
+                (Some("derive"), _) => {
+                    return Err(syn::Error::new(arg.span(), "expected `derive(Trait, ...)`"))
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        arg.span(),
+                        "unknown argument, expected `name`, `derive`, `state_prefix`, `start` or `crate`",
+                    ))
+                }
+            }
+        }
+        Ok(arguments)
+    }
+}
+
+/// the name in a string -> `name = "Countdown"`
+fn parse_ident(lit: &Lit) -> Result<Ident, syn::Error> {
+    match lit {
+        Lit::Str(text) => text.parse().map_err(|_| {
+            syn::Error::new(text.span(), "expected a single name, like `\"Countdown\"`")
+        }),
+        other => Err(syn::Error::new(other.span(), "expected a name in a string")),
+    }
+}
@@ -0,0 +1,133 @@
+//! `#[generator]` functions, which are iterators over the items of `yield_!()`
+//!
+//! ```ignore
+//! #[generator(yield = "u8")]
+//! fn countdown(from: u8) -> &'static str {
+//!     let mut current: u8 = from;
+//!     while current > 0 {
+//!         yield_!(current);
+//!         current -= 1;
+//!     }
+//!     "liftoff"
+//! }
+//! ```
+//!
+//! is a `#[freezable]` function with a different output:
+//! - the output of the freezable is `GeneratorState<u8, &'static str>`
+//! - `yield_!(current)` becomes `freeze!(GeneratorState::Yielded(current))`, `yield_!()` yields `()`
+//! - the return value is given as `GeneratorState::Complete("liftoff")`, see `finish`
+//! - `start` returns a `Generator`, which is an `Iterator<Item = u8>` over the freezable and keeps the return value.
+//!   The freezable itself is created by the hidden `__freezable_start`
+//!
+//! The other arguments are the same as the arguments of `#[freezable]`, `start` is the name of the function that
+//! creates the iterator. Everything else works like in the freezable functions, `freeze!()` and the awaited
+//! freezables freeze without an item, and the iterator skips them.
+
+use crate::arguments::Arguments;
+use crate::generate_state_machine;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote_spanned;
+use syn::{
+    parse_quote, spanned::Spanned, visit_mut::VisitMut, AttributeArgs, Ident, Item, Lit, Meta,
+    NestedMeta,
+};
+
+/// set by `#[generator]`: the types of the items and of the return value
+pub struct GeneratorTypes {
+    pub yields: syn::Type,
+    pub returns: syn::Type,
+}
+
+pub fn generator_2(args: AttributeArgs, input: Item) -> Result<TokenStream2, syn::Error> {
+    let mut func = match input {
+        Item::Fn(func) => func,
+        other => return Err(syn::Error::new(other.span(), "expected a function!")),
+    };
+    if let Some(receiver) = func.sig.receiver() {
+        return Err(syn::Error::new(
+            receiver.span(),
+            "methods can't be `#[generator]`s, use a function that takes the value as a parameter",
+        ));
+    }
+
+    // `yield` is taken out, the others are the arguments of `#[freezable]`
+    let mut yields: Option<syn::Type> = None;
+    let mut rest = vec![];
+    for arg in args {
+        match &arg {
+            NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("yield") => {
+                if yields.is_some() {
+                    return Err(syn::Error::new(
+                        arg.span(),
+                        "`yield` is given more than once",
+                    ));
+                }
+                let Lit::Str(text) = &name_value.lit else {
+                    return Err(syn::Error::new(
+                        name_value.lit.span(),
+                        "expected a type in a string, like `yield = \"u8\"`",
+                    ));
+                };
+                yields = Some(text.parse()?);
+            }
+            _ => rest.push(arg),
+        }
+    }
+    let Some(yields) = yields else {
+        return Err(syn::Error::new(
+            func.sig.ident.span(),
+            "the type of the items should be given: `#[generator(yield = \"u8\")]`",
+        ));
+    };
+    let mut arguments = Arguments::parse(rest)?;
+
+    let krate = arguments.krate.clone();
+    let returns: syn::Type = match &func.sig.output {
+        syn::ReturnType::Default => parse_quote!(()),
+        syn::ReturnType::Type(_, ty) => (**ty).clone(),
+    };
+    func.sig.output = parse_quote!(-> #krate::GeneratorState<#yields, #returns>);
+    YieldRewriter {
+        krate: &krate,
+        yields: &yields,
+    }
+    .visit_block_mut(&mut func.block);
+    arguments.generator = Some(GeneratorTypes { yields, returns });
+    generate_state_machine(func, arguments)
+}
+
+/// `yield_!(x)` -> `freeze!(GeneratorState::Yielded(x))`, the type of the items is written,
+/// so the mismatched items are reported where they are
+struct YieldRewriter<'a> {
+    krate: &'a syn::Path,
+    yields: &'a syn::Type,
+}
+
+impl VisitMut for YieldRewriter<'_> {
+    fn visit_macro_mut(&mut self, mac: &mut syn::Macro) {
+        let is_yield = mac
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "yield_");
+        if !is_yield {
+            return;
+        }
+        let span = mac.path.span();
+        let (krate, yields) = (self.krate, self.yields);
+        let item = if mac.tokens.is_empty() {
+            quote_spanned!(span=> ())
+        } else {
+            mac.tokens.clone()
+        };
+        mac.path = Ident::new("freeze", span).into();
+        mac.tokens = quote_spanned!(span=> #krate::GeneratorState::<#yields, _>::Yielded(#item));
+    }
+
+    // `yield_!()` inside of them wouldn't yield from our function
+    fn visit_expr_closure_mut(&mut self, _: &mut syn::ExprClosure) {}
+
+    fn visit_expr_async_mut(&mut self, _: &mut syn::ExprAsync) {}
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
@@ -0,0 +1,318 @@
+//! `#[freezable]` functions that store variables without a written type
+//!
+//! a variable without a type (`let next = begin + 1;`) gets a placeholder type in the builder -> `__FreezableT0`.
+//! If such a variable is stored, we can't declare the enum with it, since we don't know the type that the compiler
+//! infers for it. But the compiler can infer the type parameters of a generic enum for us:
+//! - the states are put into a hidden enum `__{name}_State`, which has a type parameter for every placeholder
+//! - the state machine becomes a closure that is created in `start`. The closure has the concrete code of the
+//!   chunks, so the compiler infers the type parameters from the variables that are stored in the variants
+//! - the state and the closure are boxed as a `dyn Freezable`, so the freezable is still of type `name`,
+//!   and it can be stored and awaited like the other ones
+//!
+//! The match arms are type checked in order, and a variable is first stored by the chunk that declares it,
+//! so its type is already known when the later chunks use it.
+//!
+//! The variables with inferred types are written as `_` by `fmt_state`, since their types are type parameters
+//! in the `Freezable` implementation, which don't implement `Debug` as far as it knows.
+//!
+//! The box needs a lifetime, so the function can have at most one lifetime, and its type parameters should
+//! outlive it (or be `'static`, if there is no lifetime).
+
+use crate::state_machine::{is_inferred, Chunk};
+use crate::{
+    construct_variant, freezable_where_clause, input_type, phantom_variant, start_functions,
+    state_machine, state_methods, variant_generator, Arguments, Forwarded,
+};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_quote, Ident};
+
+/// the placeholders of the types that are stored in the variants, in the order they are first stored
+pub fn inferred_types(chunks: &[Chunk]) -> Vec<Ident> {
+    let mut inferred: Vec<Ident> = vec![];
+    for (_, ty) in chunks.iter().flat_map(|chunk| &chunk.vars) {
+        if let syn::Type::Path(path) = ty {
+            match path.path.get_ident() {
+                Some(placeholder) if is_inferred(ty) && !inferred.contains(placeholder) => {
+                    inferred.push(placeholder.clone())
+                }
+                _ => {}
+            }
+        }
+    }
+    inferred
+}
+
+/// `'a`, `T` and `N` for `<'a, T: Clone, const N: usize>`
+fn generic_arguments(generics: &syn::Generics) -> Vec<TokenStream2> {
+    generics
+        .params
+        .iter()
+        .map(|param| match param {
+            syn::GenericParam::Lifetime(param) => {
+                let lifetime = &param.lifetime;
+                quote!(#lifetime)
+            }
+            syn::GenericParam::Type(param) => {
+                let ident = &param.ident;
+                quote!(#ident)
+            }
+            syn::GenericParam::Const(param) => {
+                let ident = &param.ident;
+                quote!(#ident)
+            }
+        })
+        .collect()
+}
+
+pub fn generate_inferred_implementation(
+    name: &Ident,
+    arguments: &Arguments,
+    forwarded: &Forwarded,
+    generics: &syn::Generics,
+    parameters: &syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>,
+    return_type: &syn::Type,
+    chunks: &[Chunk],
+) -> Result<TokenStream2, syn::Error> {
+    let inferred_types = inferred_types(chunks);
+    if let Some(derive) = arguments.derives.first() {
+        return Err(syn::Error::new_spanned(
+            derive,
+            "the traits can't be derived when the types of the stored variables are inferred, \
+             since the state machine is boxed. Write the types of the variables explicitly",
+        ));
+    }
+    let lifetimes: Vec<&syn::Lifetime> =
+        generics.lifetimes().map(|param| &param.lifetime).collect();
+    let lifetime: syn::Lifetime = match lifetimes[..] {
+        [] => parse_quote!('static),
+        [lifetime] => lifetime.clone(),
+        _ => {
+            return Err(syn::Error::new(
+                inferred_types[0].span(),
+                "the type of this variable can't be inferred in a function with more than one lifetime, \
+                 write it explicitly: `let x: u8 = ...;`",
+            ))
+        }
+    };
+    let Forwarded {
+        vis, common, docs, ..
+    } = forwarded;
+    let state_name = format_ident!("__{}_State", name);
+    let machine_name = format_ident!("__{}_Machine", name);
+
+    // the generics of the function, and a type parameter for every inferred type
+    let mut state_generics = generics.clone();
+    state_generics.params.extend(
+        inferred_types
+            .iter()
+            .map(|ty| -> syn::GenericParam { parse_quote!(#ty) }),
+    );
+    let (state_impl_generics, state_type_generics, state_where_clause) =
+        state_generics.split_for_impl();
+    let prefix = &arguments.state_prefix;
+    let krate = &arguments.krate;
+    let variants = variant_generator(prefix, chunks);
+    let phantom_variant = phantom_variant(generics).map(|variant| quote!(#variant,));
+    let first_variant = construct_variant(&state_name, prefix, chunks, 0);
+    let state_machine = state_machine(
+        &state_name,
+        arguments,
+        generics,
+        chunks,
+        &quote!(__freezable_state),
+    );
+    let state_methods = state_methods(
+        &state_name,
+        arguments,
+        generics,
+        chunks,
+        &quote!(&self.state),
+    );
+    // the compiler infers the `_`s from the code of the closure
+    let type_arguments = generic_arguments(generics);
+    let placeholders = inferred_types.iter().map(|_| quote!(_));
+    let state_type = quote!(#state_name<#(#type_arguments,)* #(#placeholders),*>);
+
+    let input_type = input_type(chunks);
+    let (input_parameter, input_parameter_type, input_argument) = match input_type {
+        Some(input_type) => (
+            quote!(, mut __freezable_input: ::core::option::Option<#input_type>),
+            quote!(, ::core::option::Option<#input_type>),
+            quote!(, ::core::option::Option::None),
+        ),
+        None => (quote!(), quote!(), quote!()),
+    };
+    let trait_object = match input_type {
+        Some(input_type) => quote!(#krate::FreezableWith<#input_type, Output = #return_type>),
+        None => quote!(#krate::Freezable<Output = #return_type>),
+    };
+
+    // the machine implements the traits for the closure, without knowing its type
+    let mut machine_generics = state_generics.clone();
+    machine_generics.params.push(parse_quote!(__F));
+    let (machine_impl_generics, _, _) = machine_generics.split_for_impl();
+    let mut machine_where_clause = freezable_where_clause(&state_generics, return_type);
+    machine_where_clause.predicates.push(parse_quote! {
+        __F: ::core::ops::FnMut(
+            &mut #state_name #state_type_generics
+            #input_parameter_type
+        ) -> ::core::result::Result<#krate::FreezableState<#return_type>, #krate::FreezableError>
+    });
+
+    // everything in the box should outlive its lifetime
+    let (impl_generics, type_generics, _) = generics.split_for_impl();
+    let mut where_clause = freezable_where_clause(generics, return_type);
+    for param in generics.type_params() {
+        let ident = &param.ident;
+        where_clause
+            .predicates
+            .push(parse_quote!(#ident: #lifetime));
+    }
+    let types = generics.type_params().map(|param| &param.ident);
+
+    let freezable_with = match input_type {
+        Some(input_type) => quote! {
+            #(#common)*
+            impl #machine_impl_generics #krate::FreezableWith<#input_type> for #machine_name<#state_name #state_type_generics, __F>
+            #machine_where_clause
+            {
+                fn unfreeze_with(
+                    &mut self,
+                    input: #input_type,
+                ) -> ::core::result::Result<#krate::FreezableState<Self::Output>, #krate::FreezableError> {
+                    (self.unfreeze)(&mut self.state, ::core::option::Option::Some(input))
+                }
+            }
+
+            #(#common)*
+            impl #impl_generics #krate::FreezableWith<#input_type> for #name #type_generics #where_clause {
+                fn unfreeze_with(
+                    &mut self,
+                    input: #input_type,
+                ) -> ::core::result::Result<#krate::FreezableState<Self::Output>, #krate::FreezableError> {
+                    #krate::FreezableWith::unfreeze_with(&mut *self.0, input)
+                }
+            }
+        },
+        None => quote!(),
+    };
+
+    let start_body = quote! {
+        let unfreeze = |__freezable_state: &mut #state_type #input_parameter|
+            -> ::core::result::Result<#krate::FreezableState<#return_type>, #krate::FreezableError> {
+            #state_machine
+        };
+        #name(
+            ::std::boxed::Box::new(#machine_name {
+                state: #first_variant,
+                unfreeze,
+            }),
+            ::core::marker::PhantomData,
+        )
+    };
+    let start_functions = start_functions(
+        arguments,
+        forwarded,
+        parameters,
+        quote!(#[allow(unused_variables, unused_mut, clippy::never_loop)]),
+        start_body,
+    );
+
+    Ok(quote! {
+        #(#common)*
+        #(#docs)*
+        #[allow(non_camel_case_types)]
+        #vis struct #name #impl_generics (
+            ::std::boxed::Box<dyn #trait_object + #lifetime>,
+            ::core::marker::PhantomData<(#(fn() -> #types,)* #(&#lifetimes (),)*)>,
+        ) #where_clause;
+
+        #(#common)*
+        #[allow(non_camel_case_types)]
+        #[doc(hidden)]
+        #vis enum #state_name #state_impl_generics #state_where_clause {
+            #(#variants,)*
+            #phantom_variant
+            Finished,
+            Cancelled,
+        }
+
+        #(#common)*
+        #[allow(non_camel_case_types)]
+        #[doc(hidden)]
+        #vis struct #machine_name<S, F> {
+            state: S,
+            unfreeze: F,
+        }
+
+        #(#common)*
+        impl #machine_impl_generics #krate::Freezable for #machine_name<#state_name #state_type_generics, __F>
+        #machine_where_clause
+        {
+            type Output = #return_type;
+
+            fn unfreeze(&mut self) -> ::core::result::Result<#krate::FreezableState<Self::Output>, #krate::FreezableError> {
+                (self.unfreeze)(&mut self.state #input_argument)
+            }
+
+            fn cancel(&mut self) {
+                self.state = #state_name::Cancelled
+            }
+
+            fn is_cancelled(&self) -> bool {
+                ::core::matches!(self.state, #state_name::Cancelled)
+            }
+
+            fn is_finished(&self) -> bool {
+                ::core::matches!(self.state, #state_name::Finished)
+            }
+
+            #state_methods
+        }
+
+        #freezable_with
+
+        #(#common)*
+        impl #impl_generics #name #type_generics #where_clause {
+            #start_functions
+        }
+
+        #(#common)*
+        impl #impl_generics #krate::Freezable for #name #type_generics #where_clause {
+            type Output = #return_type;
+
+            fn unfreeze(&mut self) -> ::core::result::Result<#krate::FreezableState<Self::Output>, #krate::FreezableError> {
+                #krate::Freezable::unfreeze(&mut *self.0)
+            }
+
+            fn cancel(&mut self) {
+                #krate::Freezable::cancel(&mut *self.0)
+            }
+
+            fn is_cancelled(&self) -> bool {
+                #krate::Freezable::is_cancelled(&*self.0)
+            }
+
+            fn is_finished(&self) -> bool {
+                #krate::Freezable::is_finished(&*self.0)
+            }
+
+            fn state_name(&self) -> &'static str {
+                #krate::Freezable::state_name(&*self.0)
+            }
+
+            fn state_index(&self) -> usize {
+                #krate::Freezable::state_index(&*self.0)
+            }
+
+            fn state_count(&self) -> usize {
+                #krate::Freezable::state_count(&*self.0)
+            }
+
+            fn fmt_state(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                #krate::Freezable::fmt_state(&*self.0, f)
+            }
+        }
+    })
+}
@@ -1,6 +1,9 @@
 //! #[freezable] and `freeze!()` macro implementations
-//! freezable-macro is not smart yet (and probably will never be). You have to write types explicitly for this macro to work.
+//! freezable-macro is not smart yet (and probably will never be). It is best to write the types of the variables explicitly,
 //! we need the types, so that we can store the variables in state machines (enum chunks).
+//! Only the variables that are still used after a `freeze!()` are stored, the others are dropped there.
+//! The types are stored as they are written, so any type can be used: `Vec<Vec<u8>>`, `std::string::String`,
+//! `[u8; 4]`, or tuples of them like `(u8, (String, [u8; 4]))`.
 //! So, for example, the code should be like this:
 //!
 //! ```ignore
@@ -20,102 +23,275 @@
 //! }
 //! ```
 //!
-//! the feature of deriving the type implicitly could be implemented to our macro, but it won't be anything original,
-//! nor directly related to the main problem of demystifying the concept of asynchronous code.
-//! It will just be a duplication of the compiler's work.
+//! deriving the types ourselves would just be a duplication of the compiler's work. But the compiler can do it for us:
+//! `let next = begin + 1;` works as well. The variables that are not stored don't need a type at all.
+//! If an untyped variable is stored, the state machine is put into a closure where the compiler infers the types,
+//! and it is boxed (see the `inferred` module). The type of the freezable is still `name`, but it costs an allocation,
+//! and the function can't have more than one lifetime then.
 //!
-//! Another point is, if you want to put something in `freeze!()` for returning it in the `frozen` state, you must put a variable,
-//! not an expression. For example:
-//! this will work `freeze!(var)`
-//! this WON'T work `freeze!(5+3)`
-//! so if you want to put `5+3` into `freeze!`, then assign this operation to a variable, and put that variable in `freeze!`
+//! `freeze!()` can also be placed inside of `if`/`else` branches, so the function may freeze only on one path:
+//!
+//! ```ignore
+//! fn freezable_conditional(begin: u8) -> u8 {
+//!     let mut next: u8 = begin;
+//!     if next < 5 {
+//!         next += 1;
+//!         freeze!(next);  // only small numbers will freeze here
+//!     }
+//!     next
+//! }
+//! ```
+//!
+//! and inside of `loop`, `while` and `for` loops, for example to freeze on every item:
+//!
+//! ```ignore
+//! fn freezable_sum(end: u8) -> u8 {
+//!     let mut sum: u8 = 0;
+//!     for i in 1..end {
+//!         sum += i;
+//!         freeze!(sum);
+//!     }
+//!     sum
+//! }
+//! ```
+//!
+//! the iterator of a `for` loop is stored in the state machine as well. Its type is known if the iterable
+//! is a variable with an explicit type, or a range of such a variable, otherwise it is inferred.
+//! The variants store the variables by their names, so a branch or a loop with a `freeze!()` inside can't shadow
+//! a variable that is declared outside of it (`let x: String` in the branch, while `x: u8` is used after the `if`).
+//! The variables that are declared inside of it can be shadowed as usual.
+//!
+//! Another point is, if you want to put something in `freeze!()` for returning it in the `frozen` state, you can put any
+//! expression in it: `freeze!(var)`, `freeze!(5+3)`, or `freeze!(text.len())`. The expression is evaluated once, right before
+//! the function freezes, so it can borrow the variables that are going to be stored in the next state.
+//!
+//! `return` works anywhere in the function, like in a normal function: it finishes the state machine,
+//! and the returned value is given in the `Finished` state. The `?` operator works the same way for the functions
+//! that return a `Result`: the error is converted with `From`, and given in the `Finished` state as `Err(error)`.
+//! In the functions that return an `Option`, `?` on a `None` finishes them with `None`. Only `Result` and `Option`
+//! are supported (the `Try` trait of the standard library is not stable), and they can't be mixed, like in any function.
+//!
+//! `freeze!()` can also receive a value, when it is used in a `let` statement: `let data: u8 = freeze!(x);`.
+//! Then `FreezableWith<u8>` is implemented as well, and `data` is the input of the `unfreeze_with(input)` call,
+//! which unfreezes the function from there.
+//!
+//! A freezable function can drive another one with `await_freezable!()`, like `.await` does for the futures:
+//!
+//! ```ignore
+//! fn freezable_delegate(begin: u8) -> u8 {
+//!     let generated: u8 = await_freezable!(freezable_generator_4::start(begin));
+//!     generated + 1
+//! }
+//! ```
+//!
+//! the awaited freezable is stored in the state machine, and every `unfreeze` is forwarded to it. Our function
+//! freezes (without a partial value) while it is frozen, and continues with its output when it is finished.
+//! The type of it is taken from `name::start(...)`, or it can be written explicitly:
+//! `await_freezable!(counter.refresh_start(2) as Counter_refresh<'a>)`, otherwise it is inferred.
+//!
+//! The same can be written as an `async fn`, then every `.await` is lowered into the same states as
+//! `await_freezable!()`, which is what rustc does for the futures:
+//!
+//! ```ignore
+//! async fn freezable_delegate(begin: u8) -> u8 {
+//!     let generated: u8 = freezable_generator_4::start(begin).await;
+//!     generated + 1
+//! }
+//! ```
+//!
+//! `start` is not `async`, it returns the freezable like for any other function. Only freezables can be awaited,
+//! and like `freeze!()`, `.await` can only be a statement (or the value of a `let`, or the return value).
+//!
+//! `#[generator(yield = "u8")]` turns a function that yields items with `yield_!(x)` into an `Iterator<Item = u8>`,
+//! and keeps its return value separately. It is a freezable function underneath, see the `generator` module.
+//!
+//! The generated enum and its `start` function are as visible as the original function. The doc comments are put on
+//! the enum, `#[cfg]` and the lint attributes (like `#[allow(dead_code)]`) on every generated item, and the other
+//! attributes (like `#[must_use]`) on `start`.
+//!
+//! The generated code can be configured with the arguments of the attribute, see the `arguments` module:
+//! `#[freezable(name = "Countdown", derive(Debug, Clone), state_prefix = "Step", start = "new")]`.
+//!
+//! The states are named `Chunk0`, `Chunk1`, ... by default, which doesn't say much in the logs of a larger function.
+//! `freeze!(@AwaitingHandshake)` (or `freeze!(@AwaitingHandshake, value)`) names the state the function is frozen at,
+//! and `state_name()` returns the name of the current state -> `"AwaitingHandshake"`, `"Finished"`.
+//! `state_index()` and `state_count()` tell how far the function is, and `debug_state()` prints the state with the
+//! variables stored in it -> `Chunk2 { num1: 4, num2: 2 }`, so a runtime can show the progress of any task.
+//!
+//! The generated code writes every path in full (`::freezable::FreezableState`, `::core::result::Result`),
+//! so nothing has to be imported next to `#[freezable]`, and the items of the user can't shadow them.
+//! A crate that re-exports `freezable` can point the macro to it with `#[freezable(crate = "my_crate::freezable")]`.
+//!
+//! Generic functions work too. The generated enum carries the type parameters, lifetimes, const generics and the
+//! where clause of the function, so it is started like `freezable_prefixes::<3>::start("hello")`.
+//!
+//! Methods can be freezable as well, if `#[freezable]` is put on both the method and its `impl` block.
+//! `fn refresh(&mut self, id: u32)` becomes `Type::refresh_start(&mut value, id)`, see the `method` module.
 //!
 //! I wanted to include only the essential logic for generating an asynchronous context into the freezable-macro.
 //! It will be expected that, the freezable-macro will lack many optimizations/features for the sake of minimalism,
 //! in the end, it is not an end product, but a learning tool to discover the concepts: async, generators, yield, etc...
 
 extern crate proc_macro;
+mod arguments;
+mod generator;
+mod inferred;
+mod liveness;
+mod method;
+mod rewrite;
+mod state_machine;
+
+use arguments::Arguments;
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::quote;
-use syn::{parse_macro_input, parse_str, spanned::Spanned, Ident, Item, Variant};
+use quote::{format_ident, quote};
+use state_machine::{Chunk, ChunkBuilder, Transition, Variable};
+use syn::{
+    parse_macro_input, parse_quote, parse_str,
+    spanned::Spanned,
+    visit::{self, Visit},
+    AttributeArgs, Ident, Item, Variant,
+};
 
 #[proc_macro_attribute]
 pub fn freezable(args: TokenStream, input: TokenStream) -> TokenStream {
-    assert!(args.is_empty());
+    let args = parse_macro_input!(args as AttributeArgs);
     let ty = parse_macro_input!(input as Item);
-    freezable_2(ty)
+    freezable_2(args, ty)
         .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
 
-fn freezable_2(input: Item) -> Result<TokenStream2, syn::Error> {
-    if let Item::Fn(func) = input {
-        let mut code_chunks = vec![vec![]];
-        let mut var_chunks = vec![vec![]]; // TODO: try complex types like `Vec<Vec<u8>>` in here, it may not be `Ident`
-        let mut freeze_returns: Vec<TokenStream2> = vec![];
-        let mut return_value = None;
-
-        // if some parameters are supplied to the function, we need to bring those to scope of every variant
-        if let Some(mut input_vars) = parse_parameters(&func) {
-            var_chunks.last_mut().unwrap().append(&mut input_vars);
-            var_chunks.push(var_chunks.last().unwrap().clone());
-        }
-
-        // parse the code inside the function, and store the necessary info in our structure
-        code_parser(
-            &func,
-            &mut code_chunks,
-            &mut var_chunks,
-            &mut freeze_returns,
-            &mut return_value,
-        );
-
-        var_chunks.pop(); // the last item is not necessary, since we are storing the variables for the next chunk
-                          // we don't need to store the variables declared in the last chunk, because there won't be a next chunk
-        let return_type = parse_return_type(&func);
-        let name = func.sig.ident.clone();
-        let variants = variant_generator(&var_chunks); // list of variants, along with their types -> `Chunk2(u8, u8)`
-        let variant_names = variant_names(var_chunks.len()); // list of variant names -> `Chunk2`
-        let first_chunk_name = variant_names[0].clone(); // necessary for the `start` function
-        let parameters = func.sig.inputs; // list of parameters along with their types -> `begin: u8`
-        let parameter_names: Vec<Ident> = var_chunks[0] // list of parameter names
-            .iter()
-            .map(|(name, _type)| name.clone())
-            .collect();
-        let var_name_chunks = var_chunks
-            .iter()
-            .map(|inner| {
-                inner
-                    .iter()
-                    .map(|(name, _ty)| name.clone())
-                    .collect::<Vec<Ident>>()
-            })
-            .collect::<Vec<Vec<Ident>>>();
+/// turns a function that yields items with `yield_!()` into an iterator, see the `generator` module
+#[proc_macro_attribute]
+pub fn generator(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as AttributeArgs);
+    let item = parse_macro_input!(input as Item);
+    generator::generator_2(args, item)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
 
-        let match_arms = generate_match_arms(
-            &name,
-            &variant_names,
-            &var_name_chunks,
-            &code_chunks,
-            &freeze_returns,
-            return_value,
-        );
+fn freezable_2(args: AttributeArgs, input: Item) -> Result<TokenStream2, syn::Error> {
+    match input {
+        Item::Fn(func) => {
+            if let Some(receiver) = func.sig.receiver() {
+                return Err(syn::Error::new(
+                    receiver.span(),
+                    "`#[freezable]` methods need `#[freezable]` on their `impl` block as well",
+                ));
+            }
+            generate_state_machine(func, Arguments::parse(args)?)
+        }
+        Item::Impl(item_impl) => {
+            if let Some(arg) = args.first() {
+                return Err(syn::Error::new(
+                    arg.span(),
+                    "the arguments should be given to the `#[freezable]` of the methods",
+                ));
+            }
+            method::freezable_impl(item_impl)
+        }
+        _ => Err(syn::Error::new(
+            input.span(),
+            "expected a function, or an `impl` block!",
+        )),
+    }
+}
 
-        generate_freezable_implementation(
+/// generates the enum, and the `Freezable` implementation of it, for the given function
+fn generate_state_machine(
+    func: syn::ItemFn,
+    arguments: Arguments,
+) -> Result<TokenStream2, syn::Error> {
+    check_signature(&func.sig)?;
+    // the `.await`s are removed when the function is split, rustc wouldn't see them in a normal function
+    if func.sig.asyncness.is_none() {
+        if let Some(span) = state_machine::find_dot_await(&func.block) {
+            return Err(syn::Error::new(
+                span,
+                "`.await` can only be used in an `async fn`, use `await_freezable!()` in a normal function",
+            ));
+        }
+    }
+    // if some parameters are supplied to the function, we need to bring those to scope of the first chunk
+    let input_vars = parse_parameters(&func)?;
+
+    // parse the code inside the function, and split it into chunks
+    let chunks = ChunkBuilder::new(input_vars, arguments.krate.clone()).build(&func.block)?;
+
+    let return_type = parse_return_type(&func);
+    check_stored_types(&chunks, &return_type)?;
+    check_labels(&chunks, &arguments.state_prefix)?;
+    check_input_types(&chunks)?;
+    let name = arguments.name.clone().unwrap_or(func.sig.ident);
+    let forwarded = Forwarded::new(func.vis, &func.attrs); // visibility and attributes -> `pub`, `#[must_use]`
+    let parameters = func.sig.inputs; // list of parameters along with their types -> `begin: u8`
+    let generics = &func.sig.generics; // generics, lifetimes and the where clause -> `<T: Clone>`
+
+    // the types of some stored variables are not written, the compiler has to infer them
+    if !inferred::inferred_types(&chunks).is_empty() {
+        return inferred::generate_inferred_implementation(
             &name,
-            &variants,
+            &arguments,
+            &forwarded,
+            generics,
             &parameters,
-            first_chunk_name,
-            &parameter_names,
             &return_type,
-            &match_arms,
-        )
-    } else {
-        Err(syn::Error::new(input.span(), "expected a function!"))
+            &chunks,
+        );
+    }
+    generate_freezable_implementation(
+        &name,
+        &arguments,
+        &forwarded,
+        generics,
+        &parameters,
+        &return_type,
+        &chunks,
+    )
+}
+
+/// the visibility and the attributes of the function, that are put on the generated items
+struct Forwarded {
+    /// the generated type and `start` are as visible as the function
+    vis: syn::Visibility,
+    /// `#[cfg]` and the lint attributes (`#[allow(dead_code)]`), they are put on every generated item
+    common: Vec<syn::Attribute>,
+    /// the doc comments describe the generated type
+    docs: Vec<syn::Attribute>,
+    /// the others (`#[must_use]`, `#[deprecated]`, `#[inline]`) are put on `start`, which replaces the function
+    start: Vec<syn::Attribute>,
+}
+
+impl Forwarded {
+    fn new(vis: syn::Visibility, attrs: &[syn::Attribute]) -> Self {
+        let mut forwarded = Forwarded {
+            vis,
+            common: vec![],
+            docs: vec![],
+            start: vec![],
+        };
+        for attr in attrs {
+            if is_common_attribute(attr) {
+                forwarded.common.push(attr.clone());
+            } else if attr.path.is_ident("doc") {
+                forwarded.docs.push(attr.clone());
+            } else {
+                forwarded.start.push(attr.clone());
+            }
+        }
+        forwarded
     }
 }
 
+/// `#[cfg]` and the lint attributes, which should be on all the items that are generated from the function
+fn is_common_attribute(attr: &syn::Attribute) -> bool {
+    ["cfg", "allow", "warn", "deny", "forbid", "expect"]
+        .iter()
+        .any(|name| attr.path.is_ident(name))
+}
+
 fn parse_return_type(func: &syn::ItemFn) -> syn::Type {
     if let syn::ReturnType::Type(_, a) = &func.sig.output {
         *a.clone()
@@ -124,243 +300,690 @@ fn parse_return_type(func: &syn::ItemFn) -> syn::Type {
     }
 }
 
-fn parse_parameters(func: &syn::ItemFn) -> Option<Vec<(Ident, Ident)>> {
-    if func.sig.inputs.is_empty() {
-        return None;
-    }
-
+fn parse_parameters(func: &syn::ItemFn) -> Result<Vec<Variable>, syn::Error> {
     let mut names_types = vec![];
     for i in func.sig.inputs.iter() {
         if let syn::FnArg::Typed(a) = i {
-            if let syn::Pat::Ident(b) = &*a.pat {
-                if let syn::Type::Path(c) = &*a.ty {
-                    names_types.push((b.ident.clone(), c.path.segments[0].ident.clone()))
-                }
-            }
+            state_machine::parse_pattern_types(&a.pat, &a.ty, &mut names_types)?;
         }
     }
-    Some(names_types)
+    Ok(names_types)
 }
 
-fn code_parser(
-    func: &syn::ItemFn,
-    code_chunks: &mut Vec<Vec<TokenStream2>>,
-    var_chunks: &mut Vec<Vec<(Ident, Ident)>>,
-    freeze_returns: &mut Vec<TokenStream2>,
-    return_value: &mut Option<syn::Expr>,
-) {
-    func.block
-        .stmts
-        .iter()
-        .for_each(|statement| match statement {
-            syn::Stmt::Local(local) => {
-                code_chunks.last_mut().unwrap().push(quote!(#statement));
-
-                var_chunks
-                    .last_mut()
-                    .unwrap()
-                    .append(&mut parse_variable_names_and_types(local));
-            }
-            syn::Stmt::Semi(e, _t) => {
-                if quote!(#e).to_string().starts_with("freeze! (") {
-                    let value = parse_freeze(e);
-                    freeze_returns.push(quote!(#value));
-                    code_chunks.push(vec![]);
-                    var_chunks.push(var_chunks.last().unwrap().clone());
-                } else {
-                    code_chunks.last_mut().unwrap().push(quote!(#statement));
-                }
-            }
-            syn::Stmt::Expr(e) => {
-                return_value.replace(e.clone());
-            }
-            _other => code_chunks.last_mut().unwrap().push(quote!(#statement)),
-        });
+/// the kinds of functions that can't be turned into a state machine
+fn check_signature(sig: &syn::Signature) -> Result<(), syn::Error> {
+    let error = |span: proc_macro2::Span, message: &str| Err(syn::Error::new(span, message));
+    if let Some(constness) = &sig.constness {
+        return error(
+            constness.span(),
+            "`const` functions can't be `#[freezable]`",
+        );
+    }
+    if let Some(unsafety) = &sig.unsafety {
+        return error(
+            unsafety.span(),
+            "`unsafe` functions are not supported by `#[freezable]`, use an `unsafe` block inside of the function",
+        );
+    }
+    if let Some(abi) = &sig.abi {
+        return error(abi.span(), "`#[freezable]` functions can't have an ABI");
+    }
+    if let Some(variadic) = &sig.variadic {
+        return error(
+            variadic.span(),
+            "`#[freezable]` functions can't be variadic",
+        );
+    }
+    Ok(())
 }
 
-fn parse_variable_names_and_types(local: &syn::Local) -> Vec<(Ident, Ident)> {
-    let mut names_types = vec![];
-
-    // if the statement is a `let` statement
-    if let syn::Pat::Type(a) = &local.pat {
-        // if it is in format -> `let a = something`
-        if let syn::Pat::Ident(b) = &*a.pat {
-            if let syn::Type::Path(c) = &*a.ty {
-                names_types.push((b.ident.clone(), c.path.segments[0].ident.clone()))
-            }
+/// the types of the stored variables and the return type should be written in the generated code as they are,
+/// so they can't have the things that only work in the signature of a function
+fn check_stored_types(chunks: &[Chunk], return_type: &syn::Type) -> Result<(), syn::Error> {
+    let mut checker = TypeChecker { error: None };
+    checker.visit_type(return_type);
+    for chunk in chunks {
+        for (_, ty) in &chunk.vars {
+            checker.visit_type(ty);
         }
+    }
+    match checker.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
 
-        // if it is in tuple format -> `let (a, b, c) = (x, y, z)`
-        if let syn::Pat::Tuple(b) = &*a.pat {
-            if let syn::Type::Tuple(c) = &*a.ty {
-                let names = b.elems.iter();
-                let types = c.elems.iter();
+/// finds elided lifetimes and `impl Trait`s in a type
+struct TypeChecker {
+    error: Option<syn::Error>,
+}
 
-                for (name, ty) in names.zip(types) {
-                    if let syn::Pat::Ident(c) = name {
-                        if let syn::Type::Path(d) = ty {
-                            names_types.push((c.ident.clone(), d.path.segments[0].ident.clone()))
-                        }
-                    }
-                }
-            }
+impl TypeChecker {
+    fn fail(&mut self, span: proc_macro2::Span, message: &str) {
+        self.error.get_or_insert(syn::Error::new(span, message));
+    }
+}
+
+impl<'ast> Visit<'ast> for TypeChecker {
+    fn visit_type_reference(&mut self, reference: &'ast syn::TypeReference) {
+        if reference.lifetime.is_none() {
+            self.fail(
+                reference.and_token.span(),
+                "the lifetime of this reference should be written explicitly, since it is stored \
+                 in the state machine: `&'a str`",
+            );
         }
-    } else {
-        panic!(
-            "{}",
-            syn::Error::new(
-                local.span(),
-                "let statement is in incorrect format. Maybe you forget to explicitly put types?"
-            )
+        visit::visit_type_reference(self, reference);
+    }
+
+    fn visit_lifetime(&mut self, lifetime: &'ast syn::Lifetime) {
+        if lifetime.ident == "_" {
+            self.fail(
+                lifetime.span(),
+                "`'_` can't be used in the types that are stored in the state machine, use a named lifetime",
+            );
+        }
+    }
+
+    fn visit_type_impl_trait(&mut self, impl_trait: &'ast syn::TypeImplTrait) {
+        self.fail(
+            impl_trait.span(),
+            "`impl Trait` can't be stored in the state machine, use a generic parameter instead: \
+             `fn name<T: Trait>(value: T)`",
         );
-        //panic!("let statement is in incorrect format. Maybe you forget to explicitly put types?");
     }
-    names_types
+
+    // function pointers and `Fn` traits have their own lifetime elision
+    fn visit_type_bare_fn(&mut self, _: &'ast syn::TypeBareFn) {}
+
+    fn visit_parenthesized_generic_arguments(
+        &mut self,
+        _: &'ast syn::ParenthesizedGenericArguments,
+    ) {
+    }
 }
 
-fn variant_generator(var_chunks: &[Vec<(Ident, Ident)>]) -> Vec<Variant> {
-    var_chunks
+/// `Chunk2`, or `{state_prefix}2` if a prefix is given in the arguments,
+/// or the label of the `freeze!(@Label)` that the chunk starts after
+fn variant_name(prefix: &str, chunk: &Chunk, index: usize) -> Ident {
+    match &chunk.label {
+        Some(label) => label.clone(),
+        None => format_ident!("{}{}", prefix, index),
+    }
+}
+
+/// the labels should not collide with each other, or with the other variants of the enum
+fn check_labels(chunks: &[Chunk], prefix: &str) -> Result<(), syn::Error> {
+    let mut names: Vec<String> = ["Finished", "Cancelled", "__Phantom"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    names.extend(
+        chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.label.is_none())
+            .map(|(i, chunk)| variant_name(prefix, chunk, i).to_string()),
+    );
+    for label in chunks.iter().filter_map(|chunk| chunk.label.as_ref()) {
+        if names.contains(&label.to_string()) {
+            return Err(syn::Error::new(
+                label.span(),
+                format!(
+                    "there is already a state named `{}`, the labels should be unique",
+                    label
+                ),
+            ));
+        }
+        names.push(label.to_string());
+    }
+    Ok(())
+}
+
+/// every `let data: T = freeze!();` should receive the same type, since it is the `R` of `FreezableWith<R>`
+fn check_input_types(chunks: &[Chunk]) -> Result<(), syn::Error> {
+    let Some(expected) = input_type(chunks) else {
+        return Ok(());
+    };
+    for (_, ty) in chunks.iter().filter_map(|chunk| chunk.input.as_ref()) {
+        if ty != expected {
+            return Err(syn::Error::new(
+                ty.span(),
+                format!(
+                    "every `freeze!()` should receive the same type, since it is given to `unfreeze_with`: \
+                     the first one receives `{}`",
+                    quote!(#expected)
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn variant_generator(prefix: &str, chunks: &[Chunk]) -> Vec<Variant> {
+    chunks
         .iter()
         .enumerate()
-        .map(|(i, vars)| {
-            let mut variant_name_str = format!("Chunk{}", i);
-            if vars.is_empty() {
-                parse_str::<Variant>(&variant_name_str).unwrap()
+        .map(|(i, chunk)| {
+            let variant_name = variant_name(prefix, chunk, i);
+            if chunk.vars.is_empty() {
+                parse_quote!(#variant_name)
             } else {
-                variant_name_str += "(";
-                for (_, var_type) in vars.iter() {
-                    variant_name_str +=
-                        &("Option<".to_owned() + &var_type.to_string() + ">" + ", ");
-                    // TODO: actually we need wrap with Option only when the type is not `Copy`, but that will require extra logic
-                }
-                variant_name_str += ")";
-                parse_str::<Variant>(&variant_name_str).unwrap()
+                let types = chunk.vars.iter().map(|(_, var_type)| var_type);
+                parse_quote!(#variant_name(#(#types),*))
             }
         })
         .collect::<Vec<Variant>>()
 }
 
-fn variant_names(chunk_amount: usize) -> Vec<Variant> {
-    (0..chunk_amount)
-        .map(|i| {
-            let variant_name_str = format!("Chunk{}", i);
-            parse_str::<Variant>(&variant_name_str).unwrap()
-        })
-        .collect::<Vec<Variant>>()
+fn variable_names(chunk: &Chunk) -> Vec<&Ident> {
+    chunk.vars.iter().map(|(name, _ty)| name).collect()
+}
+
+/// the expression for creating the variant of the given chunk, from the variables in the current scope
+/// -> `name::Chunk2(num1, num2)`
+fn construct_variant(name: &Ident, prefix: &str, chunks: &[Chunk], index: usize) -> TokenStream2 {
+    let variant_name = variant_name(prefix, &chunks[index], index);
+    let variable_names = variable_names(&chunks[index]);
+    if variable_names.is_empty() {
+        quote!(#name::#variant_name)
+    } else {
+        quote!(#name::#variant_name(#(#variable_names),*))
+    }
+}
+
+/// the code that finishes the state machine with the given value (or `()`)
+///
+/// the value of a `#[generator]` is wrapped -> `GeneratorState::Complete(value)`
+fn finish(
+    name: &Ident,
+    arguments: &Arguments,
+    state: &TokenStream2,
+    value: Option<&syn::Expr>,
+) -> syn::Expr {
+    let krate = &arguments.krate;
+    let mut value = match value {
+        Some(value) => quote!(#value),
+        None => quote!(()),
+    };
+    if arguments.generator.is_some() {
+        value = quote!(#krate::GeneratorState::Complete(#value));
+    }
+    parse_quote!({
+        *#state = #name::Finished;
+        return ::core::result::Result::Ok(#krate::FreezableState::Finished(#value));
+    })
 }
 
+/// the arms of the `match` in `unfreeze`, one for every chunk
+///
+/// `state` is the place where the enum is stored -> `self`
 fn generate_match_arms(
     name: &Ident,
-    variant_names: &[Variant],
-    var_name_chunks: &[Vec<Ident>],
-    code_chunks: &[Vec<TokenStream2>],
-    freeze_returns: &[TokenStream2],
-    return_value: Option<syn::Expr>,
+    arguments: &Arguments,
+    chunks: &[Chunk],
+    state: &TokenStream2,
 ) -> Vec<TokenStream2> {
+    let prefix = &arguments.state_prefix;
+    let krate = &arguments.krate;
     let mut match_arms = vec![];
-    for i in 0..variant_names.len() - 1 {
-        let cur_variant_name = &variant_names[i];
-        let next_variant_name = &variant_names[i + 1];
-        let cur_variable_names = &var_name_chunks[i];
-        let next_variable_names = &var_name_chunks[i + 1];
-        let cur_code_chunk = &code_chunks[i];
-        let cur_freeze_return = &freeze_returns[i];
-
-        // interpolation of Some(5) -> evaluates to 5
-        // interpolation of None -> evaluates to nothing
-        // hence, the code should be manually written for an Option interpolation
-        if freeze_returns[i].is_empty() {
-            match_arms.push(quote! {
-                #name::#cur_variant_name(#(#cur_variable_names),*) => {
-                    #(let mut #cur_variable_names = #cur_variable_names.take().expect("value is always present");)*
-                    #(#cur_code_chunk);*;
-                    *self = #name::#next_variant_name(#(Some(#next_variable_names),)*);
-                    Ok(FreezableState::Frozen(None))
-                }
-            });
+    // `return` in the original code finishes the state machine
+    let finish = |value: Option<&syn::Expr>| finish(name, arguments, state, value);
+    let rewrite_returns = |e: &syn::Expr| {
+        let mut e = e.clone();
+        rewrite::rewrite_returns_in_expr(&mut e, krate, &finish);
+        e
+    };
+    for (i, chunk) in chunks.iter().enumerate() {
+        let variant_name = variant_name(prefix, chunk, i);
+        let variable_names = variable_names(chunk);
+        // `break` and `continue` of the split loops, that are in the code of this chunk
+        let goto = |next: usize| -> syn::Expr {
+            let next_variant = construct_variant(name, prefix, chunks, next);
+            parse_quote!({
+                *#state = #next_variant;
+                continue '__freezable;
+            })
+        };
+        let code = chunk.code.iter().map(|statement| {
+            let mut statement = statement.clone();
+            rewrite::rewrite_returns(&mut statement, krate, &finish);
+            rewrite::expand_gotos(&mut statement, &goto);
+            statement
+        });
+        let pattern = if variable_names.is_empty() {
+            quote!(#name::#variant_name)
         } else {
-            match_arms.push(quote! {
-                #name::#cur_variant_name(#(#cur_variable_names),*) => {
-                    #(let mut #cur_variable_names = #cur_variable_names.take().expect("value is always present");)*
-                    #(#cur_code_chunk);*;
-                    *self = #name::#next_variant_name(#(Some(#next_variable_names),)*);
-                    Ok(FreezableState::Frozen(Some(#cur_freeze_return)))
+            quote!(#name::#variant_name(#(mut #variable_names),*))
+        };
+
+        let transition = match chunk
+            .transition
+            .as_ref()
+            .expect("every chunk is ended by the builder")
+        {
+            Transition::Freeze { value, next } => {
+                let next_variant = construct_variant(name, prefix, chunks, *next);
+                // interpolation of Some(5) -> evaluates to 5
+                // interpolation of None -> evaluates to nothing
+                // hence, the code should be manually written for an Option interpolation
+                match value.as_ref().map(rewrite_returns) {
+                    // the value is evaluated before the variables are moved into the next variant,
+                    // so it can borrow them
+                    Some(value) => quote! {
+                        let __freezable_value = #value;
+                        *#state = #next_variant;
+                        return ::core::result::Result::Ok(#krate::FreezableState::Frozen(::core::option::Option::Some(__freezable_value)));
+                    },
+                    None => quote! {
+                        *#state = #next_variant;
+                        return ::core::result::Result::Ok(#krate::FreezableState::Frozen(::core::option::Option::None));
+                    },
                 }
-            });
-        }
+            }
+            Transition::Goto(next) => {
+                let next_variant = construct_variant(name, prefix, chunks, *next);
+                quote! {
+                    *#state = #next_variant;
+                    continue '__freezable;
+                }
+            }
+            Transition::Branch {
+                condition,
+                then_chunk,
+                else_chunk,
+            } => {
+                let condition = rewrite_returns(condition);
+                let then_variant = construct_variant(name, prefix, chunks, *then_chunk);
+                let else_variant = construct_variant(name, prefix, chunks, *else_chunk);
+                quote! {
+                    if #condition {
+                        *#state = #then_variant;
+                    } else {
+                        *#state = #else_variant;
+                    }
+                    continue '__freezable;
+                }
+            }
+            Transition::Finish(return_value) => {
+                let finish = finish(return_value.as_ref().map(rewrite_returns).as_ref());
+                quote!(#finish)
+            }
+            Transition::Await {
+                child,
+                output,
+                next,
+            } => {
+                let this_variant = construct_variant(name, prefix, chunks, i);
+                let next_variant = construct_variant(name, prefix, chunks, *next);
+                let output = match output {
+                    Some((pat, Some(ty))) => quote!(let #pat: #ty = __freezable_output;),
+                    Some((pat, None)) => quote!(let #pat = __freezable_output;),
+                    None => quote!(),
+                };
+                quote! {
+                    match #krate::Freezable::unfreeze(&mut #child) {
+                        // we can't give the partial value of the child, since its type is different
+                        ::core::result::Result::Ok(#krate::FreezableState::Frozen(_)) => {
+                            *#state = #this_variant;
+                            return ::core::result::Result::Ok(#krate::FreezableState::Frozen(::core::option::Option::None));
+                        }
+                        ::core::result::Result::Ok(#krate::FreezableState::Finished(__freezable_output)) => {
+                            #output
+                            *#state = #next_variant;
+                            continue '__freezable;
+                        }
+                        ::core::result::Result::Err(error) => {
+                            *#state = #this_variant;
+                            return ::core::result::Result::Err(error);
+                        }
+                    }
+                }
+            }
+        };
+
+        // the chunk that continues after `let data: u8 = freeze!()` starts with the input
+        let (wait_input, bind_input) = match &chunk.input {
+            Some((pat, ty)) => (
+                {
+                    let this_variant = construct_variant(name, prefix, chunks, i);
+                    quote! {
+                        let ::core::option::Option::Some(__freezable_input) = __freezable_input.take() else {
+                            *#state = #this_variant;
+                            return ::core::result::Result::Err(#krate::FreezableError::MissingInput);
+                        };
+                    }
+                },
+                quote!(let #pat: #ty = __freezable_input;),
+            ),
+            None => (quote!(), quote!()),
+        };
+
+        match_arms.push(quote! {
+            #pattern => {
+                #wait_input
+                #bind_input
+                #(#code)*
+                #transition
+            }
+        });
     }
 
-    let last_variant_name = variant_names.last().unwrap();
-    let last_variable_names = var_name_chunks.last().unwrap();
-    let last_code_chunk = code_chunks.last().unwrap();
+    match_arms
+}
 
-    match_arms.push(quote! {
-            #name::#last_variant_name(#(#last_variable_names),*,) => {
-                #(let mut #last_variable_names = #last_variable_names.take().expect("value is always present");)*
-                #(#last_code_chunk;)*
-                *self = #name::Finished;
-                Ok(FreezableState::Finished(#return_value))
+/// type parameters and lifetimes of the function may not be used by any of the variables that are stored
+/// in the variants (for example, `T` of the return type), but an enum has to use all of them.
+/// This variant uses all of them, and it can never be created, since `Infallible` has no values
+fn phantom_variant(generics: &syn::Generics) -> Option<Variant> {
+    let types = generics.type_params().map(|param| &param.ident);
+    let lifetimes = generics.lifetimes().map(|param| &param.lifetime);
+    if generics.type_params().count() + generics.lifetimes().count() == 0 {
+        return None;
+    }
+    Some(parse_quote! {
+        #[doc(hidden)]
+        __Phantom(
+            ::core::marker::PhantomData<(#(fn() -> #types,)* #(&#lifetimes (),)*)>,
+            ::core::convert::Infallible,
+        )
+    })
+}
+
+/// the type of the values that are received from `freeze!()`, if any -> `let data: u8 = freeze!();`
+fn input_type(chunks: &[Chunk]) -> Option<&syn::Type> {
+    chunks
+        .iter()
+        .find_map(|chunk| chunk.input.as_ref().map(|(_, ty)| ty))
+}
+
+/// the trait requires the output to implement `Debug`, which may depend on the generics
+fn freezable_where_clause(generics: &syn::Generics, return_type: &syn::Type) -> syn::WhereClause {
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    where_clause
+        .predicates
+        .push(parse_quote!(#return_type: ::core::fmt::Debug));
+    where_clause.clone()
+}
+
+/// the body of `unfreeze`, which runs the chunks until the function freezes or finishes
+///
+/// the variables are moved out of the current variant, the `state` is `Finished` until the arm puts the next
+/// variant into it. Every arm either returns, or continues with the next chunk in the same call
+fn state_machine(
+    name: &Ident,
+    arguments: &Arguments,
+    generics: &syn::Generics,
+    chunks: &[Chunk],
+    state: &TokenStream2,
+) -> TokenStream2 {
+    let krate = &arguments.krate;
+    let match_arms = generate_match_arms(name, arguments, chunks, state);
+    let phantom_arm =
+        phantom_variant(generics).map(|_| quote!(#name::__Phantom(_, never) => match never {},));
+    quote! {
+        '__freezable: loop {
+            match ::core::mem::replace(#state, #name::Finished) {
+                #(#match_arms,)*
+                #phantom_arm
+                #name::Finished => return ::core::result::Result::Err(#krate::FreezableError::AlreadyFinished),
+                #name::Cancelled => {
+                    *#state = #name::Cancelled;
+                    return ::core::result::Result::Err(#krate::FreezableError::Cancelled);
+                }
+            }
+        }
+    }
+}
+
+/// `state_name`, `state_index`, `state_count` and `fmt_state` of the `Freezable` implementation
+///
+/// `state` is a reference to the enum -> `self`.
+/// The hidden variables (the iterators of the loops, the awaited freezables) are not written by `fmt_state`
+fn state_methods(
+    name: &Ident,
+    arguments: &Arguments,
+    generics: &syn::Generics,
+    chunks: &[Chunk],
+    state: &TokenStream2,
+) -> TokenStream2 {
+    let prefix = &arguments.state_prefix;
+    let krate = &arguments.krate;
+    let variant_names: Vec<Ident> = chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| variant_name(prefix, chunk, i))
+        .collect();
+    let texts: Vec<String> = variant_names.iter().map(|name| name.to_string()).collect();
+    let indexes = 0..chunks.len();
+    let (finished_index, cancelled_index, state_count) =
+        (chunks.len(), chunks.len() + 1, chunks.len() + 2);
+    let fmt_arms = chunks.iter().zip(&variant_names).map(|(chunk, variant_name)| {
+        let text = variant_name.to_string();
+        if chunk.vars.is_empty() {
+            return quote!(#name::#variant_name => __freezable_f.write_str(#text));
+        }
+        let bindings = chunk.vars.iter().map(|(var, _)| {
+            if var.to_string().starts_with("__freezable") {
+                quote!(_)
+            } else {
+                quote!(#var)
             }
         });
+        let fields = chunk
+            .vars
+            .iter()
+            .filter(|(var, _)| !var.to_string().starts_with("__freezable"))
+            .map(|(var, _)| {
+                let text = var.to_string();
+                quote!(.field(#text, (&#krate::__private::Captured(#var)).as_debug()))
+            });
+        quote! {
+            #name::#variant_name(#(#bindings),*) => __freezable_f.debug_struct(#text) #(#fields)* .finish()
+        }
+    });
+    let phantom_arm =
+        phantom_variant(generics).map(|_| quote!(#name::__Phantom(_, never) => match *never {},));
+
+    quote! {
+        fn state_name(&self) -> &'static str {
+            match #state {
+                #(#name::#variant_names { .. } => #texts,)*
+                #phantom_arm
+                #name::Finished => "Finished",
+                #name::Cancelled => "Cancelled",
+            }
+        }
 
-    match_arms
+        fn state_index(&self) -> usize {
+            match #state {
+                #(#name::#variant_names { .. } => #indexes,)*
+                #phantom_arm
+                #name::Finished => #finished_index,
+                #name::Cancelled => #cancelled_index,
+            }
+        }
+
+        fn state_count(&self) -> usize {
+            #state_count
+        }
+
+        fn fmt_state(&self, __freezable_f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+            #[allow(unused_imports)]
+            use #krate::__private::{DebugCaptured as _, NotDebugCaptured as _};
+            match #state {
+                #(#fmt_arms,)*
+                #phantom_arm
+                #name::Finished => __freezable_f.write_str("Finished"),
+                #name::Cancelled => __freezable_f.write_str("Cancelled"),
+            }
+        }
+    }
 }
 
-fn parse_freeze(e: &syn::Expr) -> TokenStream2 {
-    if let syn::Expr::Macro(a) = e {
-        a.mac.tokens.clone()
-    } else {
-        unreachable!()
+/// `start`, which creates the freezable from the parameters of the function. `attrs` are put on it,
+/// along with the forwarded attributes
+///
+/// `start` of a `#[generator]` creates the iterator, and the freezable is created by the hidden `__freezable_start`
+fn start_functions(
+    arguments: &Arguments,
+    forwarded: &Forwarded,
+    parameters: &syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>,
+    attrs: TokenStream2,
+    body: TokenStream2,
+) -> TokenStream2 {
+    let Forwarded { vis, start, .. } = forwarded;
+    let start_name = &arguments.start;
+    let Some(generator) = &arguments.generator else {
+        return quote! {
+            #(#start)*
+            #attrs
+            #vis fn #start_name(#parameters) -> Self {
+                #body
+            }
+        };
+    };
+
+    let krate = &arguments.krate;
+    let (yields, returns) = (&generator.yields, &generator.returns);
+    let names: Vec<Ident> = (0..parameters.len())
+        .map(|i| format_ident!("__freezable_arg{}", i))
+        .collect();
+    let types = parameters.iter().map(|parameter| match parameter {
+        syn::FnArg::Typed(typed) => &typed.ty,
+        syn::FnArg::Receiver(_) => unreachable!("methods can't be generators"),
+    });
+    let output = quote!(#krate::GeneratorState<#yields, #returns>);
+    quote! {
+        #attrs
+        fn __freezable_start(#parameters) -> Self {
+            #body
+        }
+
+        #(#start)*
+        #vis fn #start_name(#(#names: #types),*) -> #krate::Generator<Self, #yields, #returns>
+        where
+            Self: #krate::Freezable<Output = #output>,
+        {
+            #krate::Generator::new(Self::__freezable_start(#(#names),*))
+        }
     }
 }
 
 fn generate_freezable_implementation(
     name: &Ident,
-    variants: &[Variant],
+    arguments: &Arguments,
+    forwarded: &Forwarded,
+    generics: &syn::Generics,
     parameters: &syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>,
-    first_chunk_name: Variant,
-    parameter_names: &[Ident],
     return_type: &syn::Type,
-    match_arms: &[TokenStream2],
+    chunks: &[Chunk],
 ) -> Result<TokenStream2, syn::Error> {
+    let prefix = &arguments.state_prefix;
+    let variants = variant_generator(prefix, chunks); // list of variants, along with their types -> `Chunk2(u8, u8)`
+    let first_variant = construct_variant(name, prefix, chunks, 0); // necessary for the `start` function
+    let input_type = input_type(chunks);
+
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let phantom_variant = phantom_variant(generics).map(|variant| quote!(#variant,));
+    let freezable_where_clause = freezable_where_clause(generics, return_type);
+    let state_machine = state_machine(name, arguments, generics, chunks, &quote!(self));
+    let state_methods = state_methods(name, arguments, generics, chunks, &quote!(self));
+    let krate = &arguments.krate;
+    let derives = &arguments.derives;
+    let derive = (!derives.is_empty()).then(|| quote!(#[derive(#(#derives),*)]));
+    let start_functions = start_functions(
+        arguments,
+        forwarded,
+        parameters,
+        quote!(),
+        quote!(#first_variant),
+    );
+    let Forwarded {
+        vis, common, docs, ..
+    } = forwarded;
+
+    let (unfreeze, unfreeze_with) = match input_type {
+        None => (
+            quote! {
+                #[allow(unused_variables, unused_mut, clippy::never_loop)]
+                fn unfreeze(&mut self) -> ::core::result::Result<#krate::FreezableState<Self::Output>, #krate::FreezableError> {
+                    #state_machine
+                }
+            },
+            quote!(),
+        ),
+        // both `unfreeze` and `unfreeze_with` run the state machine, with or without an input
+        Some(input_type) => (
+            quote! {
+                fn unfreeze(&mut self) -> ::core::result::Result<#krate::FreezableState<Self::Output>, #krate::FreezableError> {
+                    self.__freezable_unfreeze(::core::option::Option::None)
+                }
+            },
+            quote! {
+                #(#common)*
+                impl #impl_generics #name #type_generics #where_clause {
+                    #[allow(unused_variables, unused_mut, clippy::never_loop)]
+                    fn __freezable_unfreeze(
+                        &mut self,
+                        mut __freezable_input: ::core::option::Option<#input_type>,
+                    ) -> ::core::result::Result<#krate::FreezableState<#return_type>, #krate::FreezableError> {
+                        #state_machine
+                    }
+                }
+
+                #(#common)*
+                impl #impl_generics #krate::FreezableWith<#input_type> for #name #type_generics #freezable_where_clause {
+                    fn unfreeze_with(
+                        &mut self,
+                        input: #input_type,
+                    ) -> ::core::result::Result<#krate::FreezableState<Self::Output>, #krate::FreezableError> {
+                        self.__freezable_unfreeze(::core::option::Option::Some(input))
+                    }
+                }
+            },
+        ),
+    };
+
     Ok(quote! {
+        #(#common)*
+        #(#docs)*
+        #derive
         #[allow(non_camel_case_types)]
-        pub enum #name {
+        #vis enum #name #impl_generics #where_clause {
             #(#variants,)*
+            #phantom_variant
             Finished,
             Cancelled,
         }
 
-        impl #name {
-            pub fn start(#parameters) -> Self {
-                #name::#first_chunk_name(#(Some(#parameter_names)),*)
-            }
+        #(#common)*
+        impl #impl_generics #name #type_generics #where_clause {
+            #start_functions
         }
 
-        #[allow(unused_variables)]
-        #[allow(unused_mut)]
-        impl Freezable for #name {
+        #unfreeze_with
+
+        #(#common)*
+        impl #impl_generics #krate::Freezable for #name #type_generics #freezable_where_clause {
             type Output = #return_type;
 
-            fn unfreeze(&mut self) -> Result<FreezableState<Self::Output>, FreezableError> {
-                match self {
-                    #(#match_arms,)*
-                    #name::Finished => Err(FreezableError::AlreadyFinished),
-                    #name::Cancelled => Err(FreezableError::Cancelled),
-                }
-            }
+            #unfreeze
 
             fn cancel(&mut self) {
                 *self = #name::Cancelled
             }
 
             fn is_cancelled(&self) -> bool {
-                matches!(self, #name::Cancelled)
+                ::core::matches!(self, #name::Cancelled)
             }
 
             fn is_finished(&self) -> bool {
-                matches!(self, #name::Finished)
+                ::core::matches!(self, #name::Finished)
             }
+
+            #state_methods
         }
     })
 }
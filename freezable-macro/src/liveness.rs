@@ -0,0 +1,154 @@
+//! removes the variables from the chunks, that are not used anymore
+//!
+//! the builder stores every variable in scope in the variant of a chunk. But a variable only needs to be stored
+//! if it is live: it is used in that chunk, or in one of the chunks that can come after it. Otherwise it is
+//! dropped at the end of the chunk, instead of being moved from variant to variant for nothing.
+//!
+//! The uses are found by the names of the variables. So a field or a method with the same name as a variable
+//! counts as a use as well, which is fine: storing a variable that is not needed is wasteful, but not wrong.
+//!
+//! The variables whose names start with an underscore (`_guard`) are always stored. They are usually not used,
+//! but kept for the code that runs when they are dropped, so they should live until the end of their scope.
+
+use crate::rewrite;
+use crate::state_machine::{Chunk, Transition};
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use std::collections::HashSet;
+use syn::Lit;
+
+/// keeps only the live variables in the chunks
+pub fn remove_dead_variables(chunks: &mut [Chunk]) {
+    let used: Vec<HashSet<String>> = chunks.iter().map(used_names).collect();
+    let successors: Vec<Vec<usize>> = chunks.iter().map(successors).collect();
+
+    // a variable is live in a chunk if it is stored in it, and it is used in that chunk or it is live
+    // in one of the next chunks. The loops make the chunks depend on each other,
+    // so it is repeated until nothing changes
+    let mut live: Vec<HashSet<String>> = vec![HashSet::new(); chunks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..chunks.len()).rev() {
+            let needed: HashSet<String> = chunks[i]
+                .vars
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .filter(|name| {
+                    used[i].contains(name)
+                        || successors[i].iter().any(|&next| live[next].contains(name))
+                        || (name.starts_with('_') && name != "_")
+                })
+                .collect();
+            if needed.len() != live[i].len() {
+                live[i] = needed;
+                changed = true;
+            }
+        }
+    }
+
+    for (chunk, live) in chunks.iter_mut().zip(live) {
+        chunk
+            .vars
+            .retain(|(name, _)| live.contains(&name.to_string()));
+    }
+}
+
+/// the chunks that can be continued from the given chunk
+fn successors(chunk: &Chunk) -> Vec<usize> {
+    // `break` and `continue` that are in the code of the chunk
+    let mut successors: Vec<usize> = chunk.code.iter().flat_map(rewrite::goto_targets).collect();
+    match chunk
+        .transition
+        .as_ref()
+        .expect("every chunk is ended by the builder")
+    {
+        Transition::Freeze { next, .. } | Transition::Goto(next) => successors.push(*next),
+        Transition::Branch {
+            then_chunk,
+            else_chunk,
+            ..
+        } => successors.extend([*then_chunk, *else_chunk]),
+        Transition::Finish(_) => {}
+        // the awaiting chunk is repeated until the awaited freezable is finished,
+        // it stores its own variables
+        Transition::Await { next, .. } => successors.push(*next),
+    }
+    successors
+}
+
+/// the names that are used in the code and the transition of the chunk
+fn used_names(chunk: &Chunk) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for statement in &chunk.code {
+        collect_names(statement.to_token_stream(), &mut names);
+    }
+    match chunk
+        .transition
+        .as_ref()
+        .expect("every chunk is ended by the builder")
+    {
+        Transition::Freeze { value, .. } => collect_names(value.to_token_stream(), &mut names),
+        Transition::Branch { condition, .. } => {
+            collect_names(condition.to_token_stream(), &mut names)
+        }
+        Transition::Finish(value) => collect_names(value.to_token_stream(), &mut names),
+        Transition::Await { child, .. } => {
+            names.insert(child.to_string());
+        }
+        Transition::Goto(_) => {}
+    }
+    names
+}
+
+/// every identifier in the tokens, including the ones inside of macro calls
+fn collect_names(tokens: TokenStream, names: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                names.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => collect_names(group.stream(), names),
+            TokenTree::Literal(literal) => {
+                if let Lit::Str(text) = Lit::new(literal) {
+                    collect_format_names(&text.value(), names);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// the variables in the inline arguments of the format strings: `format!("{x}")`, `println!("{x:>width$}")`
+///
+/// every string is checked, not only the ones given to the formatting macros,
+/// since we can't know which macros are formatting their arguments
+fn collect_format_names(text: &str, names: &mut HashSet<String>) {
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        // `{{` is an escaped brace
+        if let Some(after) = rest.strip_prefix('{') {
+            rest = after;
+            continue;
+        }
+        let end = rest.find('}').unwrap_or(rest.len());
+        let (argument, spec) = rest[..end].split_once(':').unwrap_or((&rest[..end], ""));
+        insert_if_name(argument.trim(), names);
+        // the width and the precision can be given by a variable as well: `{x:>width$.precision$}`
+        for part in spec.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')) {
+            if let Some(name) = part.strip_suffix('$') {
+                insert_if_name(name, names);
+            }
+        }
+        rest = &rest[end..];
+    }
+}
+
+fn insert_if_name(text: &str, names: &mut HashSet<String>) {
+    if text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        names.insert(text.to_string());
+    }
+}
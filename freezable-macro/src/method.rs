@@ -0,0 +1,291 @@
+//! `#[freezable]` on the methods of an `impl` block
+//!
+//! the generated enum can't be declared inside of an `impl` block, so `#[freezable]` should be put on the
+//! `impl` block as well. Then, for every method that is marked with `#[freezable]`:
+//! - an enum named `{Type}_{method}` is generated next to the `impl` block, `self` is stored in it like a parameter
+//! - the method is replaced with `{method}_start`, which creates the enum from `self` and the other parameters
+//!
+//! ```ignore
+//! #[freezable]
+//! impl Counter {
+//!     #[freezable]
+//!     fn refresh(&mut self, step: u32) -> u32 {
+//!         self.count += step;
+//!         freeze!(self.count);
+//!         self.count + step
+//!     }
+//! }
+//!
+//! let mut refresh = counter.refresh_start(2); // or `Counter::refresh_start(&mut counter, 2)`
+//! ```
+//!
+//! a borrowed receiver is stored as a reference, so the enum gets a lifetime for it. The lifetime of the receiver
+//! is used if it is written explicitly (`&'a mut self`), otherwise a new one is added.
+//!
+//! Inside of the generated implementation, `self` and `Self` would refer to the enum. So `self` is renamed,
+//! and `Self` is replaced with the type of the `impl` block (`Stack::<T>` in `Self { .. }` and `Self(..)`,
+//! since `Stack<T> { .. }` is not an expression).
+
+use crate::arguments::Arguments;
+use proc_macro2::{Delimiter, Group, Spacing, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse_quote, punctuated::Punctuated, FnArg, Ident, ImplItem, ImplItemMethod, ItemImpl, Lifetime,
+};
+
+/// name of the variable that stores `self` in the state machine
+const SELF_NAME: &str = "__freezable_self";
+/// lifetime of a borrowed receiver, if it is not written explicitly
+const SELF_LIFETIME: &str = "'__freezable_self";
+
+pub fn freezable_impl(mut item_impl: ItemImpl) -> Result<TokenStream2, syn::Error> {
+    if let Some((_, path, _)) = &item_impl.trait_ {
+        return Err(syn::Error::new_spanned(
+            path,
+            "`#[freezable]` can't be used on trait implementations, the trait doesn't have the `_start` methods",
+        ));
+    }
+    let type_name = type_name(&item_impl.self_ty)?;
+
+    let mut state_machines = vec![];
+    for item in item_impl.items.iter_mut() {
+        if let ImplItem::Method(method) = item {
+            if let Some(position) = method.attrs.iter().position(is_freezable_attribute) {
+                let attr = method.attrs.remove(position);
+                let args = method_arguments(&attr)?;
+                let (start_method, state_machine) = freezable_method(
+                    &item_impl.generics,
+                    &item_impl.self_ty,
+                    &type_name,
+                    method,
+                    args,
+                )?;
+                *method = start_method;
+                state_machines.push(state_machine);
+            }
+        }
+    }
+
+    Ok(quote! {
+        #item_impl
+        #(#state_machines)*
+    })
+}
+
+/// `#[freezable]` or `#[freezable_macro::freezable]`
+fn is_freezable_attribute(attr: &syn::Attribute) -> bool {
+    attr.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "freezable")
+}
+
+/// the arguments of `#[freezable(...)]` on a method, see the `arguments` module
+fn method_arguments(attr: &syn::Attribute) -> Result<Arguments, syn::Error> {
+    if attr.tokens.is_empty() {
+        return Ok(Arguments::default());
+    }
+    match attr.parse_meta()? {
+        syn::Meta::List(list) => Arguments::parse(list.nested.into_iter().collect()),
+        other => Err(syn::Error::new_spanned(
+            other,
+            "expected `#[freezable]` or `#[freezable(...)]`",
+        )),
+    }
+}
+
+/// `Counter` for `impl Counter`, `Stack` for `impl<T> Stack<T>`
+fn type_name(self_ty: &syn::Type) -> Result<Ident, syn::Error> {
+    if let syn::Type::Path(path) = self_ty {
+        if let Some(segment) = path.path.segments.last() {
+            return Ok(segment.ident.clone());
+        }
+    }
+    Err(syn::Error::new_spanned(
+        self_ty,
+        "expected the name of a type for a `#[freezable]` impl block",
+    ))
+}
+
+/// returns the `_start` method, that will replace the original method,
+/// and the state machine that is generated from the original method
+fn freezable_method(
+    impl_generics: &syn::Generics,
+    self_ty: &syn::Type,
+    type_name: &Ident,
+    method: &ImplItemMethod,
+    args: Arguments,
+) -> Result<(ImplItemMethod, TokenStream2), syn::Error> {
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None => format_ident!("{}_{}", type_name, method.sig.ident),
+    };
+    let start = args.start.clone();
+    let mut start_sig = method.sig.clone();
+    start_sig.ident = format_ident!("{}_start", method.sig.ident);
+    // `start` returns the freezable, only the state machine is `async` (where `.await` is lowered)
+    start_sig.asyncness = None;
+
+    let mut inputs = Punctuated::<FnArg, syn::token::Comma>::new(); // parameters of the state machine
+    let mut arguments = vec![]; // passed from the `_start` method to the state machine
+    let mut self_lifetime = None; // added to the generics, if the receiver has no explicit lifetime
+    for (i, arg) in start_sig.inputs.iter_mut().enumerate() {
+        match arg {
+            FnArg::Receiver(receiver) => {
+                let self_name = Ident::new(SELF_NAME, receiver.self_token.span);
+                let self_type: syn::Type = match &mut receiver.reference {
+                    Some((_, lifetime)) => {
+                        if lifetime.is_none() {
+                            let new_lifetime =
+                                Lifetime::new(SELF_LIFETIME, receiver.self_token.span);
+                            self_lifetime = Some(new_lifetime.clone());
+                            *lifetime = Some(new_lifetime);
+                        }
+                        let mutability = receiver.mutability;
+                        parse_quote!(&#lifetime #mutability #self_ty)
+                    }
+                    None => {
+                        // `mut self` is moved into the state machine, it doesn't need to be mutable here
+                        receiver.mutability = None;
+                        self_ty.clone()
+                    }
+                };
+                inputs.push(parse_quote!(#self_name: #self_type));
+                arguments.push(quote!(self));
+            }
+            FnArg::Typed(typed) => {
+                let pat = replace_self(typed.pat.to_token_stream(), self_ty);
+                let ty = replace_self(typed.ty.to_token_stream(), self_ty);
+                inputs.push(parse_quote!(#pat: #ty));
+
+                let argument = match &*typed.pat {
+                    syn::Pat::Ident(pat) => pat.ident.clone(),
+                    _ => format_ident!("__freezable_arg{}", i),
+                };
+                typed.pat = parse_quote!(#argument);
+                arguments.push(quote!(#argument));
+            }
+        }
+    }
+
+    if let Some(lifetime) = self_lifetime {
+        start_sig.generics.params.insert(0, parse_quote!(#lifetime));
+    }
+    let generics = merge_generics(impl_generics, &start_sig.generics);
+    let (_, type_generics, _) = generics.split_for_impl();
+    let turbofish = type_generics.as_turbofish();
+    start_sig.output = parse_quote!(-> #name #type_generics);
+
+    let attrs = &method.attrs;
+    let vis = &method.vis;
+    let start_method = parse_quote! {
+        #(#attrs)*
+        #vis #start_sig {
+            #name #turbofish::#start(#(#arguments),*)
+        }
+    };
+
+    let output = replace_self(method.sig.output.to_token_stream(), self_ty);
+    let block = replace_self(method.block.to_token_stream(), self_ty);
+    let func = syn::ItemFn {
+        // the other attributes stay on the `_start` method
+        attrs: attrs
+            .iter()
+            .filter(|attr| crate::is_common_attribute(attr))
+            .cloned()
+            .collect(),
+        vis: method.vis.clone(),
+        sig: syn::Signature {
+            ident: name,
+            generics,
+            inputs,
+            output: syn::parse2(output)?,
+            ..method.sig.clone()
+        },
+        block: Box::new(syn::parse2(block)?),
+    };
+    let state_machine = crate::generate_state_machine(func, args)?;
+
+    Ok((start_method, state_machine))
+}
+
+/// the generics of the state machine: the generics of the `impl` block, and of the method
+/// (lifetimes have to come before the other parameters)
+fn merge_generics(impl_generics: &syn::Generics, method_generics: &syn::Generics) -> syn::Generics {
+    let params: Vec<syn::GenericParam> = impl_generics
+        .params
+        .iter()
+        .cloned()
+        .chain(method_generics.params.iter().cloned())
+        .collect();
+    let (lifetimes, others): (Vec<_>, Vec<_>) = params
+        .into_iter()
+        .partition(|param| matches!(param, syn::GenericParam::Lifetime(_)));
+
+    let mut generics: syn::Generics = parse_quote!(<#(#lifetimes,)* #(#others),*>);
+    let predicates: Vec<syn::WherePredicate> = impl_generics
+        .where_clause
+        .iter()
+        .chain(method_generics.where_clause.iter())
+        .flat_map(|where_clause| where_clause.predicates.iter().cloned())
+        .collect();
+    if !predicates.is_empty() {
+        generics.make_where_clause().predicates.extend(predicates);
+    }
+    generics
+}
+
+/// renames `self` in the given tokens, and replaces `Self` with the type of the `impl` block
+/// (`self::module` paths are kept as they are)
+fn replace_self(tokens: TokenStream2, self_ty: &syn::Type) -> TokenStream2 {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut replaced = TokenStream2::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(group) => {
+                let mut new_group =
+                    Group::new(group.delimiter(), replace_self(group.stream(), self_ty));
+                new_group.set_span(group.span());
+                replaced.extend([TokenTree::Group(new_group)]);
+            }
+            TokenTree::Ident(ident) if ident == "self" && !is_path_separator(tokens.peek()) => {
+                replaced.extend([TokenTree::Ident(Ident::new(SELF_NAME, ident.span()))]);
+            }
+            // `Self::new()` -> `<Counter>::new()`
+            TokenTree::Ident(ident) if ident == "Self" && is_path_separator(tokens.peek()) => {
+                replaced.extend(quote!(<#self_ty>));
+            }
+            // `Self { items }` -> `Stack::<T> { items }`, the generics need a turbofish in the expressions
+            TokenTree::Ident(ident) if ident == "Self" && is_constructor(tokens.peek()) => {
+                let constructor = turbofish_type(self_ty);
+                replaced.extend(quote!(#constructor));
+            }
+            TokenTree::Ident(ident) if ident == "Self" => replaced.extend(quote!(#self_ty)),
+            token => replaced.extend([token]),
+        }
+    }
+    replaced
+}
+
+/// `Self { .. }` or `Self(..)`
+fn is_constructor(token: Option<&TokenTree>) -> bool {
+    matches!(token, Some(TokenTree::Group(group)) if matches!(group.delimiter(), Delimiter::Brace | Delimiter::Parenthesis))
+}
+
+/// `Stack<T>` -> `Stack::<T>`, which can be written in both the expressions and the types
+fn turbofish_type(self_ty: &syn::Type) -> syn::Type {
+    let mut self_ty = self_ty.clone();
+    if let syn::Type::Path(path) = &mut self_ty {
+        for segment in path.path.segments.iter_mut() {
+            if let syn::PathArguments::AngleBracketed(arguments) = &mut segment.arguments {
+                arguments.colon2_token = Some(Default::default());
+            }
+        }
+    }
+    self_ty
+}
+
+/// the first `:` of a `::`
+fn is_path_separator(token: Option<&TokenTree>) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == ':' && punct.spacing() == Spacing::Joint)
+}
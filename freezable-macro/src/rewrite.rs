@@ -0,0 +1,272 @@
+//! rewrites the parts of the original code, that can't be put into the match arms as they are
+//!
+//! when a loop is split into chunks, it is not a loop anymore. The `break` and `continue` statements
+//! that belong to it are jumps to other chunks now. If these statements are in a part of the code that
+//! is not split into chunks (for example in an `if` without a `freeze!()` inside), they are replaced with a
+//! `__freezable_goto!(chunk_index)` marker. The marker is expanded into the code that switches to
+//! the given chunk, when the match arms are generated.
+//!
+//! A `return` inside of the original function would return from `unfreeze` with the wrong type,
+//! so it is replaced with the code that finishes the state machine with the returned value.
+//! The same goes for the `?` operator, which finishes the state machine with the error (converted with `From`),
+//! or with `None` for the functions that return an `Option`. It is done with the `Try` and `FromResidual` traits
+//! of `freezable::__private`, since the ones of the standard library are not stable yet.
+
+use proc_macro2::Span;
+use syn::{
+    parse_quote, parse_quote_spanned,
+    spanned::Spanned,
+    visit::{self, Visit},
+    visit_mut::{self, VisitMut},
+    Expr, Lifetime, Stmt,
+};
+
+const GOTO_MARKER: &str = "__freezable_goto";
+
+/// A loop that is split into chunks
+pub struct LoopTarget {
+    pub label: Option<Lifetime>,
+    /// `continue` jumps to this chunk
+    pub continue_chunk: usize,
+    /// `break` jumps to this chunk, `None` if there is no `break` for this loop
+    pub break_chunk: Option<usize>,
+}
+
+impl LoopTarget {
+    pub fn matches(&self, label: &Option<Lifetime>) -> bool {
+        label.is_none() || *label == self.label
+    }
+}
+
+/// `__freezable_goto!(3)`
+pub fn goto_marker(chunk: usize) -> Expr {
+    let chunk = syn::LitInt::new(&chunk.to_string(), Span::call_site());
+    parse_quote!(__freezable_goto!(#chunk))
+}
+
+/// replaces the `break` and `continue` statements of the split loops with `goto` markers
+pub fn rewrite_jumps(statement: &mut Stmt, targets: &[LoopTarget]) -> Result<(), syn::Error> {
+    let mut rewriter = JumpRewriter::new(targets);
+    rewriter.visit_stmt_mut(statement);
+    rewriter.result()
+}
+
+/// same as `rewrite_jumps`, for an expression (like the condition of an `if`)
+pub fn rewrite_jumps_in_expr(e: &mut Expr, targets: &[LoopTarget]) -> Result<(), syn::Error> {
+    let mut rewriter = JumpRewriter::new(targets);
+    rewriter.visit_expr_mut(e);
+    rewriter.result()
+}
+
+/// replaces the `goto` markers with the given code
+pub fn expand_gotos(statement: &mut Stmt, goto: &dyn Fn(usize) -> Expr) {
+    GotoExpander { goto }.visit_stmt_mut(statement);
+}
+
+/// the chunks that the `goto` markers in the statement jump to
+pub fn goto_targets(statement: &Stmt) -> Vec<usize> {
+    let mut finder = GotoFinder { targets: vec![] };
+    finder.visit_stmt(statement);
+    finder.targets
+}
+
+/// checks whether the body of a loop has a `break` statement that belongs to that loop
+pub fn has_break(body: &syn::Block, label: &Option<Lifetime>) -> bool {
+    let mut finder = BreakFinder {
+        label,
+        nested_loops: 0,
+        found: false,
+    };
+    finder.visit_block(body);
+    finder.found
+}
+
+/// replaces the `return` expressions and the `?` operators with the given code, which finishes the state machine
+pub fn rewrite_returns(
+    statement: &mut Stmt,
+    krate: &syn::Path,
+    finish: &dyn Fn(Option<&Expr>) -> Expr,
+) {
+    ReturnRewriter { krate, finish }.visit_stmt_mut(statement);
+}
+
+/// same as `rewrite_returns`, for an expression (like the value of a `freeze!()`)
+pub fn rewrite_returns_in_expr(
+    e: &mut Expr,
+    krate: &syn::Path,
+    finish: &dyn Fn(Option<&Expr>) -> Expr,
+) {
+    ReturnRewriter { krate, finish }.visit_expr_mut(e);
+}
+
+struct JumpRewriter<'a> {
+    targets: &'a [LoopTarget],
+    /// loops that are not split into chunks, an unlabeled `break` inside of them belongs to them
+    nested_loops: usize,
+    error: Option<syn::Error>,
+}
+
+impl<'a> JumpRewriter<'a> {
+    fn new(targets: &'a [LoopTarget]) -> Self {
+        JumpRewriter {
+            targets,
+            nested_loops: 0,
+            error: None,
+        }
+    }
+
+    fn result(self) -> Result<(), syn::Error> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn target(&self, label: &Option<Lifetime>) -> Option<&LoopTarget> {
+        if label.is_none() && self.nested_loops > 0 {
+            return None;
+        }
+        self.targets
+            .iter()
+            .rev()
+            .find(|target| target.matches(label))
+    }
+}
+
+impl VisitMut for JumpRewriter<'_> {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        match e {
+            Expr::Break(b) => match self.target(&b.label) {
+                Some(_) if b.expr.is_some() => {
+                    self.error.get_or_insert(syn::Error::new_spanned(
+                        &b.expr,
+                        "`break` with a value is not supported for the loops that have `freeze!()` inside",
+                    ));
+                }
+                Some(target) => {
+                    let chunk = target
+                        .break_chunk
+                        .expect("the loop has a chunk after it, since there is a `break`");
+                    *e = goto_marker(chunk);
+                }
+                None => visit_mut::visit_expr_mut(self, e),
+            },
+            Expr::Continue(c) => match self.target(&c.label) {
+                Some(target) => *e = goto_marker(target.continue_chunk),
+                None => visit_mut::visit_expr_mut(self, e),
+            },
+            Expr::Loop(_) | Expr::While(_) | Expr::ForLoop(_) => {
+                self.nested_loops += 1;
+                visit_mut::visit_expr_mut(self, e);
+                self.nested_loops -= 1;
+            }
+            // jumps inside of closures and async blocks can't belong to our loops
+            Expr::Closure(_) | Expr::Async(_) => {}
+            _ => visit_mut::visit_expr_mut(self, e),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+struct GotoExpander<'a> {
+    goto: &'a dyn Fn(usize) -> Expr,
+}
+
+impl VisitMut for GotoExpander<'_> {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        if let Expr::Macro(m) = e {
+            if m.mac.path.is_ident(GOTO_MARKER) {
+                let chunk: syn::LitInt = m.mac.parse_body().expect("marker is created by us");
+                let chunk = chunk.base10_parse().expect("marker is created by us");
+                *e = (self.goto)(chunk);
+                return;
+            }
+        }
+        visit_mut::visit_expr_mut(self, e);
+    }
+}
+
+struct ReturnRewriter<'a> {
+    krate: &'a syn::Path,
+    finish: &'a dyn Fn(Option<&Expr>) -> Expr,
+}
+
+impl VisitMut for ReturnRewriter<'_> {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        match e {
+            Expr::Return(r) => {
+                if let Some(value) = &mut r.expr {
+                    self.visit_expr_mut(value);
+                }
+                *e = (self.finish)(r.expr.as_deref());
+            }
+            Expr::Try(t) => {
+                self.visit_expr_mut(&mut t.expr);
+                let krate = self.krate;
+                // the span of `?`, so the mismatched types (`?` on an `Option` in a function that returns a
+                // `Result`) are reported there
+                let residual: Expr = parse_quote_spanned! {t.question_token.span()=>
+                    #krate::__private::FromResidual::from_residual(__freezable_residual)
+                };
+                let finish = (self.finish)(Some(&residual));
+                let value = &t.expr;
+                *e = parse_quote! {
+                    match #krate::__private::Try::branch(#value) {
+                        ::core::ops::ControlFlow::Continue(__freezable_value) => __freezable_value,
+                        ::core::ops::ControlFlow::Break(__freezable_residual) => #finish,
+                    }
+                };
+            }
+            // `return` and `?` inside of closures and async blocks don't return from our function
+            Expr::Closure(_) | Expr::Async(_) => {}
+            _ => visit_mut::visit_expr_mut(self, e),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+struct GotoFinder {
+    targets: Vec<usize>,
+}
+
+impl<'ast> Visit<'ast> for GotoFinder {
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        if mac.path.is_ident(GOTO_MARKER) {
+            let chunk: syn::LitInt = mac.parse_body().expect("marker is created by us");
+            self.targets
+                .push(chunk.base10_parse().expect("marker is created by us"));
+        }
+    }
+}
+
+struct BreakFinder<'a> {
+    label: &'a Option<Lifetime>,
+    nested_loops: usize,
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for BreakFinder<'_> {
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        match e {
+            Expr::Break(b) => {
+                let belongs_to_loop = match &b.label {
+                    None => self.nested_loops == 0,
+                    label => label == self.label,
+                };
+                self.found |= belongs_to_loop;
+                visit::visit_expr(self, e);
+            }
+            Expr::Loop(_) | Expr::While(_) | Expr::ForLoop(_) => {
+                self.nested_loops += 1;
+                visit::visit_expr(self, e);
+                self.nested_loops -= 1;
+            }
+            Expr::Closure(_) | Expr::Async(_) => {}
+            _ => visit::visit_expr(self, e),
+        }
+    }
+
+    fn visit_item(&mut self, _: &'ast syn::Item) {}
+}
@@ -0,0 +1,999 @@
+//! splits the body of a `#[freezable]` function into chunks
+//!
+//! every chunk will become a variant of the generated enum, and an arm of the `match` in `unfreeze`.
+//! A chunk knows:
+//! - the variables that are in scope at the beginning of it (these are stored in the variant,
+//!   the ones that are not used anymore are removed later by the `liveness` module)
+//! - the code that should be run
+//! - where to go after the code is run (freeze, jump to another chunk, or finish the function)
+//!
+//! A `freeze!()` on the top level of the function simply ends the current chunk, and starts a new one.
+//! But `freeze!()` can also be inside an `if`/`else` branch, and then the next chunk depends on the branch
+//! that is taken at runtime. So the condition of such an `if` ends the current chunk, each branch gets its own
+//! chunks, and both branches continue with the chunk that comes after the `if`.
+//!
+//! Loops with a `freeze!()` inside are split in the same way. The loop starts with a new chunk
+//! (the loop header), and the end of the body jumps back to it. `while` and `for` loops decide in their
+//! header whether to run the body again, or to jump to the chunk after the loop.
+//! The variables of the loop (including the iterator of a `for` loop) are carried in the variants.
+//!
+//! `await_freezable!()` stores the awaited freezable in a hidden variable, and gets its own chunk, which
+//! unfreezes it. The chunk is repeated until the awaited freezable is finished.
+//!
+//! The variables without a written type (`let x = 5;`, or an iterator whose type is not known) get a placeholder
+//! type -> `__FreezableT0`. If such a variable has to be stored, the compiler infers the type for us,
+//! see the `inferred` module.
+//!
+//! Jumping from one chunk to another without freezing is done in the same `unfreeze` call.
+
+use crate::liveness;
+use crate::rewrite::{self, LoopTarget};
+use quote::format_ident;
+use syn::{parse_quote, spanned::Spanned, visit::Visit, Expr, Ident, Stmt};
+
+/// A variable that is stored in a variant, along with its type
+pub type Variable = (Ident, syn::Type);
+
+/// A piece of the original function, that will be run in a single match arm
+pub struct Chunk {
+    /// variables that are in scope when this chunk starts, they will be stored in the variant
+    pub vars: Vec<Variable>,
+    /// statements of the original function that belongs to this chunk
+    pub code: Vec<Stmt>,
+    /// `let data: u8 = freeze!();` -> the value given to `unfreeze_with` is bound to `data: u8`,
+    /// when the function continues from this chunk
+    pub input: Option<(syn::Pat, syn::Type)>,
+    /// decided at the end of the chunk, `None` only while the chunk is being built
+    pub transition: Option<Transition>,
+    /// `freeze!(@AwaitingHandshake)` -> the variant of the chunk after the `freeze!()` is named `AwaitingHandshake`
+    pub label: Option<Ident>,
+}
+
+/// What happens after the code of a chunk is run
+pub enum Transition {
+    /// `freeze!()` is called: store the variables in the next chunk, and return the `Frozen` state
+    /// with the value of the expression given to `freeze!()` (if any)
+    Freeze { value: Option<Expr>, next: usize },
+    /// continue with the next chunk in the same `unfreeze` call
+    Goto(usize),
+    /// an `if` (or the header of a loop) that has a `freeze!()` inside,
+    /// continue with one of the branches in the same `unfreeze` call
+    Branch {
+        condition: Expr,
+        then_chunk: usize,
+        else_chunk: usize,
+    },
+    /// the function is finished, return the `Finished` state with this value (or `()`)
+    Finish(Option<Expr>),
+    /// `await_freezable!()`: unfreeze the child freezable that is stored in this chunk.
+    /// Stay in this chunk and return the `Frozen` state while the child is frozen,
+    /// bind its output to the pattern and continue with the next chunk when it is finished
+    Await {
+        child: Ident,
+        output: Option<(syn::Pat, Option<syn::Type>)>,
+        next: usize,
+    },
+}
+
+/// Walks over the statements of the function, and builds the chunks
+pub struct ChunkBuilder {
+    pub chunks: Vec<Chunk>,
+    /// the chunk we are putting the statements into
+    current: usize,
+    /// the variables that are visible at the current point of the function
+    scope: Vec<Variable>,
+    /// the loops (with a `freeze!()` inside) that we are currently in, the innermost one is the last
+    loops: Vec<LoopTarget>,
+    /// the variables of the scopes around the split blocks (branches, loop bodies) that we are currently in.
+    /// They can't be shadowed in the split blocks, see `declare`
+    enclosing: Vec<Ident>,
+    /// used for naming the iterators of the `for` loops
+    for_loop_count: usize,
+    /// used for naming the freezables that are awaited
+    child_count: usize,
+    /// used for naming the placeholders of the inferred types
+    inferred_count: usize,
+    /// the path of the `freezable` crate -> `::freezable`
+    krate: syn::Path,
+}
+
+impl ChunkBuilder {
+    /// the parameters of the function are in the scope of the first chunk
+    pub fn new(parameters: Vec<Variable>, krate: syn::Path) -> Self {
+        let mut builder = ChunkBuilder {
+            chunks: vec![],
+            current: 0,
+            scope: parameters,
+            loops: vec![],
+            enclosing: vec![],
+            for_loop_count: 0,
+            child_count: 0,
+            inferred_count: 0,
+            krate,
+        };
+        builder.current = builder.new_chunk();
+        builder
+    }
+
+    /// parses the body of the function, the last expression (if any) will be the return value
+    pub fn build(mut self, block: &syn::Block) -> Result<Vec<Chunk>, syn::Error> {
+        self.parse_block(block, true)?;
+        liveness::remove_dead_variables(&mut self.chunks);
+        Ok(self.chunks)
+    }
+
+    /// creates an empty chunk, which captures the variables in the current scope
+    fn new_chunk(&mut self) -> usize {
+        self.chunks.push(Chunk {
+            vars: self.scope.clone(),
+            code: vec![],
+            input: None,
+            transition: None,
+            label: None,
+        });
+        self.chunks.len() - 1
+    }
+
+    /// ends the current chunk with the given transition
+    fn end_chunk(&mut self, transition: Transition) {
+        self.chunks[self.current].transition = Some(transition);
+    }
+
+    fn is_ended(&self) -> bool {
+        self.chunks[self.current].transition.is_some()
+    }
+
+    fn push_code(&mut self, mut code: Stmt) -> Result<(), syn::Error> {
+        rewrite::rewrite_jumps(&mut code, &self.loops)?;
+        self.chunks[self.current].code.push(code);
+        Ok(())
+    }
+
+    /// brings a new variable into the scope
+    ///
+    /// a variable can hide the old one with the same name, but not in a split block if the old one is declared
+    /// outside of it: the variants store the variables by their names, so the old one would be lost at the
+    /// `freeze!()`, and the chunk after the block would get the new one
+    fn declare(&mut self, name: Ident, ty: syn::Type) -> Result<(), syn::Error> {
+        if self.enclosing.contains(&name) {
+            return Err(syn::Error::new(
+                name.span(),
+                format!(
+                    "`{name}` shadows a variable that is declared outside of this block, which is not supported \
+                     when there is a `freeze!()` inside of the block. Use another name for one of them"
+                ),
+            ));
+        }
+        // shadowing: the new variable hides the old one with the same name
+        self.scope.retain(|(existing, _)| *existing != name);
+        self.scope.push((name, ty));
+        Ok(())
+    }
+
+    /// the variables in the scope can't be shadowed until `leave_split_block` is called,
+    /// returns what should be given to it
+    fn enter_split_block(&mut self) -> usize {
+        let depth = self.enclosing.len();
+        self.enclosing
+            .extend(self.scope.iter().map(|(name, _)| name.clone()));
+        depth
+    }
+
+    fn leave_split_block(&mut self, depth: usize) {
+        self.enclosing.truncate(depth);
+    }
+
+    /// brings the variables of a pattern into the scope -> `(a, b): (u8, String)`
+    fn declare_pattern(&mut self, pat: &syn::Pat, ty: &syn::Type) -> Result<(), syn::Error> {
+        let mut names_types = vec![];
+        parse_pattern_types(pat, ty, &mut names_types)?;
+        for (name, ty) in names_types {
+            self.declare(name, ty)?;
+        }
+        Ok(())
+    }
+
+    /// a placeholder for a type that will be inferred by the compiler, the span is used for the errors
+    fn inferred_type(&mut self, span: proc_macro2::Span) -> syn::Type {
+        let placeholder = Ident::new(&format!("{}{}", INFERRED_PREFIX, self.inferred_count), span);
+        self.inferred_count += 1;
+        parse_quote!(#placeholder)
+    }
+
+    /// brings the variables of a pattern without a type into the scope -> `let (a, b) = (5, 6);`.
+    /// Every variable gets its own inferred type, so any pattern can be used
+    fn declare_inferred_pattern(&mut self, pat: &syn::Pat) -> Result<(), syn::Error> {
+        let mut finder = BindingFinder::default();
+        finder.visit_pat(pat);
+        for binding in finder.bindings {
+            if let Some(by_ref) = &binding.by_ref {
+                return Err(syn::Error::new(
+                    by_ref.span(),
+                    "`ref` bindings can't be stored in the state machine, borrow the value with `&` instead",
+                ));
+            }
+            let ty = self.inferred_type(binding.ident.span());
+            self.declare(binding.ident, ty)?;
+        }
+        Ok(())
+    }
+
+    /// parses the statements of a block
+    ///
+    /// if `is_tail` is true, the block is the body of the function (or a branch at the end of the function),
+    /// so the last expression of it is the return value
+    fn parse_block(&mut self, block: &syn::Block, is_tail: bool) -> Result<(), syn::Error> {
+        let last = block.stmts.len().saturating_sub(1);
+        for (i, statement) in block.stmts.iter().enumerate() {
+            if self.is_ended() {
+                // everything after a `return` is unreachable, we don't have a chunk to put it in
+                break;
+            }
+            let is_tail_expr = is_tail && i == last;
+            match statement {
+                // `let output: u8 = await_freezable!(child);` or `let output: u8 = child.await;`
+                Stmt::Local(local) if local_init(local).is_some_and(is_awaiting) => {
+                    let (pat, ty) = match &local.pat {
+                        syn::Pat::Type(typed) => ((*typed.pat).clone(), Some((*typed.ty).clone())),
+                        other => (other.clone(), None),
+                    };
+                    self.await_freezable(local_init(local).unwrap(), Some((pat, ty)))?;
+                }
+                // `let data: u8 = freeze!(x);`
+                Stmt::Local(local) if local_macro(local).is_some_and(is_freeze) => {
+                    let syn::Pat::Type(typed) = &local.pat else {
+                        return Err(syn::Error::new(
+                            local.pat.span(),
+                            "the type of the value that is received from `freeze!()` should be written: \
+                             `let data: u8 = freeze!();`",
+                        ));
+                    };
+                    self.freeze(local_macro(local).unwrap())?;
+                    self.receive((*typed.pat).clone(), (*typed.ty).clone())?;
+                }
+                Stmt::Local(local) => {
+                    if let Some((_, init)) = &local.init {
+                        check_no_freeze(init)?;
+                    }
+                    self.push_code(statement.clone())?;
+                    match &local.pat {
+                        syn::Pat::Type(typed) => self.declare_pattern(&typed.pat, &typed.ty)?,
+                        other => self.declare_inferred_pattern(other)?,
+                    }
+                }
+                Stmt::Semi(e, _) => self.parse_expr_statement(e, false, statement.clone())?,
+                Stmt::Expr(e) if is_block_like(e) => {
+                    self.parse_expr_statement(e, is_tail_expr, statement.clone())?
+                }
+                // the last expression of a branch, which is in the statement position
+                Stmt::Expr(e) => {
+                    let code = Stmt::Semi(e.clone(), Default::default());
+                    self.parse_expr_statement(e, is_tail_expr, code)?
+                }
+                Stmt::Item(syn::Item::Macro(item)) if is_freeze(&item.mac) => {
+                    self.freeze(&item.mac)?
+                }
+                Stmt::Item(syn::Item::Macro(item)) if is_await(&item.mac) => {
+                    let awaiting = Expr::Macro(syn::ExprMacro {
+                        attrs: vec![],
+                        mac: item.mac.clone(),
+                    });
+                    self.await_freezable(&awaiting, None)?
+                }
+                _other => self.push_code(statement.clone())?,
+            }
+        }
+
+        if is_tail && !self.is_ended() {
+            // function without a return value
+            self.end_chunk(Transition::Finish(None));
+        }
+        Ok(())
+    }
+
+    /// parses an expression in the statement position, if `is_tail_expr` is true,
+    /// it is the return value of the function
+    ///
+    /// `code` is the statement itself, which will be put into the current chunk as it is,
+    /// if there is no `freeze!()` in it
+    fn parse_expr_statement(
+        &mut self,
+        e: &Expr,
+        is_tail_expr: bool,
+        code: Stmt,
+    ) -> Result<(), syn::Error> {
+        match e {
+            Expr::Macro(m) if is_freeze(&m.mac) => {
+                self.freeze(&m.mac)?;
+                if is_tail_expr {
+                    self.end_chunk(Transition::Finish(None));
+                }
+            }
+            _ if is_awaiting(e) && is_tail_expr => {
+                // the output of the awaited freezable is the return value
+                let output = format_ident!("__freezable_output");
+                self.await_freezable(e, Some((parse_quote!(#output), None)))?;
+                self.end_chunk(Transition::Finish(Some(parse_quote!(#output))));
+            }
+            _ if is_awaiting(e) => self.await_freezable(e, None)?,
+            Expr::If(expr_if) if contains_freeze(e) => self.parse_if(expr_if, is_tail_expr)?,
+            Expr::Block(b) if b.label.is_none() && contains_freeze(e) => {
+                let outer_scope = self.scope.clone();
+                let depth = self.enter_split_block();
+                self.parse_block(&b.block, is_tail_expr)?;
+                self.leave_split_block(depth);
+                self.scope = outer_scope;
+            }
+            Expr::Loop(l) if contains_freeze(e) => {
+                self.parse_loop(&l.label, None, &l.body, is_tail_expr)?
+            }
+            Expr::While(w) if contains_freeze(e) => {
+                if let Expr::Let(_) = &*w.cond {
+                    return Err(syn::Error::new(
+                        w.cond.span(),
+                        "`while let` is not supported when there is a `freeze!()` inside of it",
+                    ));
+                }
+                check_no_freeze(&w.cond)?;
+                let mut condition = (*w.cond).clone();
+                rewrite::rewrite_jumps_in_expr(&mut condition, &self.loops)?;
+                self.parse_loop(&w.label, Some(condition), &w.body, is_tail_expr)?
+            }
+            Expr::ForLoop(f) if contains_freeze(e) => self.parse_for_loop(f, is_tail_expr)?,
+            Expr::Break(b) if self.jump_target(&b.label).is_some() => {
+                if let Some(value) = &b.expr {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "`break` with a value is not supported for the loops that have `freeze!()` inside",
+                    ));
+                }
+                let target = self.jump_target(&b.label).unwrap();
+                let exit = target
+                    .break_chunk
+                    .expect("the loop has a chunk after it, since there is a `break`");
+                self.end_chunk(Transition::Goto(exit));
+            }
+            Expr::Return(r) => {
+                if let Some(value) = &r.expr {
+                    check_no_freeze(value)?;
+                }
+                let mut value = r.expr.as_deref().cloned();
+                if let Some(value) = &mut value {
+                    rewrite::rewrite_jumps_in_expr(value, &self.loops)?;
+                }
+                self.end_chunk(Transition::Finish(value));
+            }
+            Expr::Continue(c) if self.jump_target(&c.label).is_some() => {
+                let start = self.jump_target(&c.label).unwrap().continue_chunk;
+                self.end_chunk(Transition::Goto(start));
+            }
+            _ if is_tail_expr => {
+                check_no_freeze(e)?;
+                let mut value = e.clone();
+                rewrite::rewrite_jumps_in_expr(&mut value, &self.loops)?;
+                self.end_chunk(Transition::Finish(Some(value)));
+            }
+            _ => {
+                check_no_freeze(e)?;
+                self.push_code(code)?
+            }
+        }
+        Ok(())
+    }
+
+    /// the current chunk is frozen, the rest of the code goes into a new chunk
+    fn freeze(&mut self, mac: &syn::Macro) -> Result<(), syn::Error> {
+        let (label, value) = parse_freeze(mac)?;
+        let next = self.new_chunk();
+        self.chunks[next].label = label;
+        self.end_chunk(Transition::Freeze { value, next });
+        self.current = next;
+        Ok(())
+    }
+
+    /// the value that is given to `unfreeze_with` is bound to the pattern, when the current chunk starts.
+    /// The variables of the pattern are not stored in the current chunk, they are created in it
+    fn receive(&mut self, pat: syn::Pat, ty: syn::Type) -> Result<(), syn::Error> {
+        self.declare_pattern(&pat, &ty)?;
+        self.chunks[self.current].input = Some((pat, ty));
+        Ok(())
+    }
+
+    /// the awaited freezable is stored in a hidden variable, and it is unfreezed in its own chunk,
+    /// until it is finished. Then its output is bound to the given pattern (if any).
+    /// The type of a single variable is `<Child as Freezable>::Output`, if it is not written.
+    /// Otherwise (or if the type of the child is not known either) the types are inferred
+    fn await_freezable(
+        &mut self,
+        awaiting: &Expr,
+        output: Option<(syn::Pat, Option<syn::Type>)>,
+    ) -> Result<(), syn::Error> {
+        let (mut child_expr, child_type) = parse_await(awaiting)?;
+        let child_type = match child_type {
+            Some(child_type) => child_type,
+            None => self.inferred_type(child_expr.span()),
+        };
+        check_no_freeze(&child_expr)?;
+        rewrite::rewrite_jumps_in_expr(&mut child_expr, &self.loops)?;
+        let child = format_ident!("__freezable_child{}", self.child_count);
+        self.child_count += 1;
+
+        self.push_code(parse_quote! {
+            let mut #child = #child_expr;
+        })?;
+        self.declare(child.clone(), child_type.clone())?;
+        let await_chunk = self.new_chunk();
+        self.end_chunk(Transition::Goto(await_chunk));
+        self.current = await_chunk;
+
+        // the child is not needed after it is finished
+        self.scope.retain(|(name, _)| *name != child);
+        let output = match output {
+            Some((pat, Some(ty))) => {
+                self.declare_pattern(&pat, &ty)?;
+                Some((pat, Some(ty)))
+            }
+            Some((syn::Pat::Ident(pat), None)) if !is_inferred(&child_type) => {
+                let pat = syn::Pat::Ident(pat);
+                let krate = &self.krate;
+                let ty: syn::Type = parse_quote!(<#child_type as #krate::Freezable>::Output);
+                self.declare_pattern(&pat, &ty)?;
+                Some((pat, Some(ty)))
+            }
+            Some((pat, None)) => {
+                self.declare_inferred_pattern(&pat)?;
+                Some((pat, None))
+            }
+            None => None,
+        };
+        let next = self.new_chunk();
+        self.end_chunk(Transition::Await {
+            child,
+            output,
+            next,
+        });
+        self.current = next;
+        Ok(())
+    }
+
+    /// the innermost split loop that a `break` or `continue` with the given label belongs to
+    fn jump_target(&self, label: &Option<syn::Lifetime>) -> Option<&LoopTarget> {
+        self.loops.iter().rev().find(|target| target.matches(label))
+    }
+
+    /// splits the `if` into chunks: the condition ends the current chunk,
+    /// every branch starts with a new chunk, and both of them continue from the chunk after the `if`
+    fn parse_if(&mut self, expr_if: &syn::ExprIf, is_tail_expr: bool) -> Result<(), syn::Error> {
+        if let Expr::Let(_) = &*expr_if.cond {
+            return Err(syn::Error::new(
+                expr_if.cond.span(),
+                "`if let` is not supported when there is a `freeze!()` inside of it",
+            ));
+        }
+        check_no_freeze(&expr_if.cond)?;
+
+        let outer_scope = self.scope.clone();
+        let depth = self.enter_split_block();
+        let condition_chunk = self.current;
+        let mut unfinished_branches = vec![];
+
+        let then_chunk = self.new_chunk();
+        self.current = then_chunk;
+        self.parse_block(&expr_if.then_branch, is_tail_expr)?;
+        if !self.is_ended() {
+            unfinished_branches.push(self.current);
+        }
+        self.scope = outer_scope.clone();
+
+        let else_chunk = match &expr_if.else_branch {
+            Some((_, else_branch)) => {
+                let else_chunk = self.new_chunk();
+                self.current = else_chunk;
+                match &**else_branch {
+                    // `else if`
+                    Expr::If(nested_if) => self.parse_if(nested_if, is_tail_expr)?,
+                    Expr::Block(b) => self.parse_block(&b.block, is_tail_expr)?,
+                    other => {
+                        let code = Stmt::Semi(other.clone(), Default::default());
+                        self.parse_expr_statement(other, is_tail_expr, code)?
+                    }
+                }
+                if !self.is_ended() {
+                    unfinished_branches.push(self.current);
+                }
+                self.scope = outer_scope;
+                Some(else_chunk)
+            }
+            None => None,
+        };
+        self.leave_split_block(depth);
+
+        let mut condition = (*expr_if.cond).clone();
+        rewrite::rewrite_jumps_in_expr(&mut condition, &self.loops)?;
+        if let (Some(else_chunk), true) = (else_chunk, unfinished_branches.is_empty()) {
+            // all the branches returned, nothing continues after the `if`
+            self.chunks[condition_chunk].transition = Some(Transition::Branch {
+                condition,
+                then_chunk,
+                else_chunk,
+            });
+            return Ok(());
+        }
+
+        // the chunk that continues after the `if`
+        let next_chunk = self.new_chunk();
+        for branch in unfinished_branches {
+            self.chunks[branch].transition = Some(Transition::Goto(next_chunk));
+        }
+        self.chunks[condition_chunk].transition = Some(Transition::Branch {
+            condition,
+            then_chunk,
+            else_chunk: else_chunk.unwrap_or(next_chunk),
+        });
+        self.current = next_chunk;
+
+        if is_tail_expr {
+            // `if` without `else` at the end of the function, the function returns `()`
+            self.end_chunk(Transition::Finish(None));
+        }
+        Ok(())
+    }
+
+    /// splits a `loop` or a `while` loop into chunks
+    ///
+    /// the loop starts with a new chunk (the loop header), so that we can jump back to it.
+    /// For a `while` loop, the header only checks the condition, and jumps into the body or after the loop
+    fn parse_loop(
+        &mut self,
+        label: &Option<syn::Label>,
+        condition: Option<Expr>,
+        body: &syn::Block,
+        is_tail_expr: bool,
+    ) -> Result<(), syn::Error> {
+        let label = label.as_ref().map(|l| l.name.clone());
+        let outer_scope = self.scope.clone();
+        let has_exit = condition.is_some() || rewrite::has_break(body, &label);
+
+        let header = self.new_chunk();
+        self.end_chunk(Transition::Goto(header));
+        self.current = header;
+        // the chunk after the loop is created before the body, so that `break` knows where to jump
+        let exit = if has_exit {
+            Some(self.new_chunk())
+        } else {
+            None
+        };
+
+        let body_chunk = match condition {
+            Some(condition) => {
+                let body_chunk = self.new_chunk();
+                self.end_chunk(Transition::Branch {
+                    condition,
+                    then_chunk: body_chunk,
+                    else_chunk: exit.expect("`while` loops always have an exit"),
+                });
+                body_chunk
+            }
+            None => header,
+        };
+
+        let depth = self.enter_split_block();
+        self.parse_loop_body(label, header, body_chunk, exit, body)?;
+        self.leave_split_block(depth);
+        self.scope = outer_scope;
+        self.continue_after_loop(exit, is_tail_expr);
+        Ok(())
+    }
+
+    /// splits a `for` loop into chunks
+    ///
+    /// the iterator is stored in the variants as a hidden variable, and the header of the loop
+    /// calls `next()` on it, to decide whether to run the body again, or to jump after the loop.
+    /// The element is put into the scope of the body
+    fn parse_for_loop(
+        &mut self,
+        for_loop: &syn::ExprForLoop,
+        is_tail_expr: bool,
+    ) -> Result<(), syn::Error> {
+        check_no_freeze(&for_loop.expr)?;
+        let label = for_loop.label.as_ref().map(|l| l.name.clone());
+        let iterable_type = self.iterable_type(&for_loop.expr);
+        // the iterable stays in the scope: a `Copy` one (like a slice) can be used after the loop,
+        // and a moved one is not stored unless it is used again, which is the usual "use of moved value" error
+        let outer_scope = self.scope.clone();
+
+        let iterator_type: syn::Type = match &iterable_type {
+            Some(iterable_type) => {
+                parse_quote!(<#iterable_type as ::core::iter::IntoIterator>::IntoIter)
+            }
+            None => self.inferred_type(for_loop.expr.span()),
+        };
+        let iterator = format_ident!("__freezable_iter{}", self.for_loop_count);
+        self.for_loop_count += 1;
+
+        let iterable = &for_loop.expr;
+        self.push_code(parse_quote! {
+            let mut #iterator = ::core::iter::IntoIterator::into_iter(#iterable);
+        })?;
+        self.declare(iterator.clone(), iterator_type.clone())?;
+
+        let header = self.new_chunk();
+        self.end_chunk(Transition::Goto(header));
+        self.current = header;
+        // the chunk after the loop does not have the iterator
+        let loop_scope = std::mem::replace(&mut self.scope, outer_scope.clone());
+        let exit = self.new_chunk();
+        self.scope = loop_scope;
+        // the element is declared in the body, it can't shadow a variable outside of the loop either
+        let depth = self.enter_split_block();
+
+        let pattern = &for_loop.pat;
+        match pattern {
+            syn::Pat::Ident(element)
+                if iterable_type.is_some()
+                    && element.by_ref.is_none()
+                    && element.subpat.is_none() =>
+            {
+                let element_type: syn::Type =
+                    parse_quote!(<#iterator_type as ::core::iter::Iterator>::Item);
+                self.declare(element.ident.clone(), element_type)?
+            }
+            other => self.declare_inferred_pattern(other)?,
+        }
+        let body_chunk = self.new_chunk();
+        self.end_chunk(Transition::Branch {
+            condition: parse_quote! {
+                let ::core::option::Option::Some(#pattern) = ::core::iter::Iterator::next(&mut #iterator)
+            },
+            then_chunk: body_chunk,
+            else_chunk: exit,
+        });
+
+        self.parse_loop_body(label, header, body_chunk, Some(exit), &for_loop.body)?;
+        self.leave_split_block(depth);
+        self.scope = outer_scope;
+        self.continue_after_loop(Some(exit), is_tail_expr);
+        Ok(())
+    }
+
+    /// the type of the iterable of a `for` loop
+    ///
+    /// we have to store the iterator in the variants, so we have to know its type.
+    /// It is known if the iterable is a variable, or a range of a variable (or of a literal with a suffix),
+    /// otherwise it is inferred
+    fn iterable_type(&self, iterable: &Expr) -> Option<syn::Type> {
+        match iterable {
+            Expr::Paren(inner) => self.iterable_type(&inner.expr),
+            Expr::Range(range) => {
+                let bound_type = [&range.from, &range.to]
+                    .into_iter()
+                    .flatten()
+                    .find_map(|bound| self.bound_type(bound))?;
+                match (&range.from, &range.to, &range.limits) {
+                    (Some(_), Some(_), syn::RangeLimits::HalfOpen(_)) => {
+                        Some(parse_quote!(::core::ops::Range<#bound_type>))
+                    }
+                    (Some(_), Some(_), syn::RangeLimits::Closed(_)) => {
+                        Some(parse_quote!(::core::ops::RangeInclusive<#bound_type>))
+                    }
+                    (Some(_), None, _) => Some(parse_quote!(::core::ops::RangeFrom<#bound_type>)),
+                    _ => None,
+                }
+            }
+            other => self.bound_type(other),
+        }
+    }
+
+    /// the type of a variable in the scope, or of a literal with a suffix (`5u8`)
+    ///
+    /// the placeholder of an inferred type doesn't help, we can't write `<__FreezableT0 as IntoIterator>`
+    /// without knowing that it is an iterable
+    fn bound_type(&self, e: &Expr) -> Option<syn::Type> {
+        match e {
+            Expr::Path(path) => {
+                let name = path.path.get_ident()?;
+                self.scope
+                    .iter()
+                    .find(|(var, _)| var == name)
+                    .map(|(_, ty)| ty.clone())
+                    .filter(|ty| !is_inferred(ty))
+            }
+            Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Int(int),
+                ..
+            }) if !int.suffix().is_empty() => syn::parse_str(int.suffix()).ok(),
+            _ => None,
+        }
+    }
+
+    /// parses the body of a loop, which starts at the `body_chunk`, and jumps back to the `header` at the end
+    fn parse_loop_body(
+        &mut self,
+        label: Option<syn::Lifetime>,
+        header: usize,
+        body_chunk: usize,
+        exit: Option<usize>,
+        body: &syn::Block,
+    ) -> Result<(), syn::Error> {
+        self.loops.push(LoopTarget {
+            label,
+            continue_chunk: header,
+            break_chunk: exit,
+        });
+        self.current = body_chunk;
+        self.parse_block(body, false)?;
+        if !self.is_ended() {
+            self.end_chunk(Transition::Goto(header));
+        }
+        self.loops.pop();
+        Ok(())
+    }
+
+    /// the rest of the code goes into the chunk after the loop
+    ///
+    /// if there is no such chunk (a `loop` without a `break`), the rest of the code is unreachable
+    fn continue_after_loop(&mut self, exit: Option<usize>, is_tail_expr: bool) {
+        if let Some(exit) = exit {
+            self.current = exit;
+            if is_tail_expr {
+                // a loop at the end of the function, the function returns `()`
+                self.end_chunk(Transition::Finish(None));
+            }
+        }
+    }
+}
+
+/// pairs the variables in the pattern with their types, the types are kept as they are written
+///
+/// it can be in format -> `a: Vec<Vec<u8>>`
+/// or in tuple format -> `(a, (b, c)): (u8, (String, [u8; 4]))`
+pub fn parse_pattern_types(
+    pat: &syn::Pat,
+    ty: &syn::Type,
+    names_types: &mut Vec<Variable>,
+) -> Result<(), syn::Error> {
+    match (pat, ty) {
+        (_, syn::Type::Paren(inner)) => parse_pattern_types(pat, &inner.elem, names_types),
+        (syn::Pat::Ident(name), _) => {
+            if let Some(by_ref) = &name.by_ref {
+                return Err(syn::Error::new(
+                    by_ref.span(),
+                    "`ref` bindings can't be stored in the state machine, borrow the value with `&` instead",
+                ));
+            }
+            if let Some((at, _)) = &name.subpat {
+                return Err(syn::Error::new(
+                    at.span(),
+                    "`@` patterns are not supported in `#[freezable]` functions, use a separate `let` statement",
+                ));
+            }
+            names_types.push((name.ident.clone(), ty.clone()));
+            Ok(())
+        }
+        // `_` doesn't bind anything, there is nothing to store
+        (syn::Pat::Wild(_), _) => Ok(()),
+        (syn::Pat::Tuple(names), syn::Type::Tuple(types))
+            if names.elems.len() == types.elems.len() =>
+        {
+            for (name, ty) in names.elems.iter().zip(types.elems.iter()) {
+                parse_pattern_types(name, ty, names_types)?;
+            }
+            Ok(())
+        }
+        (syn::Pat::Tuple(_), _) => Err(syn::Error::new(
+            ty.span(),
+            "the type of a tuple pattern should be written as a tuple of the same length: `(a, b): (u8, String)`",
+        )),
+        _ => Err(syn::Error::new(
+            pat.span(),
+            "this pattern is not supported in `#[freezable]` functions, only variables and tuples of them \
+             can be used, like `(a, (b, c)): (u8, (u8, u8))`",
+        )),
+    }
+}
+
+/// expressions that can be used as a statement without a semicolon
+fn is_block_like(e: &Expr) -> bool {
+    matches!(
+        e,
+        Expr::If(_)
+            | Expr::Block(_)
+            | Expr::Match(_)
+            | Expr::Loop(_)
+            | Expr::While(_)
+            | Expr::ForLoop(_)
+            | Expr::Unsafe(_)
+            | Expr::TryBlock(_)
+    )
+}
+
+/// the names of the placeholders for the inferred types -> `__FreezableT0`
+pub const INFERRED_PREFIX: &str = "__FreezableT";
+
+/// whether the type is a placeholder for an inferred type
+pub fn is_inferred(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path
+            .path
+            .get_ident()
+            .is_some_and(|ident| ident.to_string().starts_with(INFERRED_PREFIX)),
+        _ => false,
+    }
+}
+
+/// the variables that are bound by a pattern -> `a` and `b` in `Point { x: a, y: (b, _) }`
+#[derive(Default)]
+struct BindingFinder {
+    bindings: Vec<syn::PatIdent>,
+}
+
+impl<'ast> Visit<'ast> for BindingFinder {
+    fn visit_pat_ident(&mut self, binding: &'ast syn::PatIdent) {
+        self.bindings.push(binding.clone());
+        syn::visit::visit_pat_ident(self, binding);
+    }
+
+    // the paths and the expressions in a pattern (`Point { .. }`, `1..=5`) don't bind anything
+    fn visit_path(&mut self, _: &'ast syn::Path) {}
+
+    fn visit_expr(&mut self, _: &'ast Expr) {}
+}
+
+/// the label and the expression inside of `freeze!()`, both are optional:
+/// `freeze!()`, `freeze!(next)`, `freeze!(@Waiting)` or `freeze!(@Waiting, next)`
+fn parse_freeze(mac: &syn::Macro) -> Result<(Option<Ident>, Option<Expr>), syn::Error> {
+    let parser = |input: syn::parse::ParseStream| {
+        let mut label = None;
+        if input.peek(syn::Token![@]) {
+            input.parse::<syn::Token![@]>()?;
+            label = Some(input.parse::<Ident>()?);
+            if input.is_empty() {
+                return Ok((label, None));
+            }
+            input.parse::<syn::Token![,]>()?;
+        }
+        if input.is_empty() {
+            return Ok((label, None));
+        }
+        let value = input.parse::<Expr>()?;
+        Ok((label, Some(value)))
+    };
+    syn::parse::Parser::parse2(parser, mac.tokens.clone())
+}
+
+/// `freeze!()` or `freezable::freeze!()`
+fn is_freeze(mac: &syn::Macro) -> bool {
+    mac.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "freeze")
+}
+
+/// `await_freezable!()` or `freezable::await_freezable!()`
+fn is_await(mac: &syn::Macro) -> bool {
+    mac.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "await_freezable")
+}
+
+/// the expression that initializes the variables of a `let` statement
+fn local_init(local: &syn::Local) -> Option<&Expr> {
+    local.init.as_ref().map(|(_, init)| &**init)
+}
+
+/// the macro call that initializes the variables of a `let` statement -> `let data: u8 = freeze!();`
+fn local_macro(local: &syn::Local) -> Option<&syn::Macro> {
+    match local_init(local) {
+        Some(Expr::Macro(m)) => Some(&m.mac),
+        _ => None,
+    }
+}
+
+/// `await_freezable!(child)`, or `child.await` in an `async fn`
+fn is_awaiting(e: &Expr) -> bool {
+    match e {
+        Expr::Macro(m) => is_await(&m.mac),
+        Expr::Await(_) => true,
+        _ => false,
+    }
+}
+
+/// the freezable inside of `await_freezable!()` (or before `.await`), and its type
+///
+/// the type is taken from the path of the `start` function: `other::start(5)` is of type `other`,
+/// or it can be written explicitly: `await_freezable!(counter.refresh_start(2) as Counter_refresh<'a>)`,
+/// `(counter.refresh_start(2) as Counter_refresh<'a>).await`. Otherwise, it is inferred
+fn parse_await(awaiting: &Expr) -> Result<(Expr, Option<syn::Type>), syn::Error> {
+    let child: Expr = match awaiting {
+        Expr::Macro(m) => m.mac.parse_body()?,
+        Expr::Await(dot_await) => match &*dot_await.base {
+            Expr::Paren(paren) => (*paren.expr).clone(),
+            base => base.clone(),
+        },
+        other => unreachable!("{} is not awaiting", quote::quote!(#other)),
+    };
+    match &child {
+        Expr::Cast(cast) => return Ok(((*cast.expr).clone(), Some((*cast.ty).clone()))),
+        Expr::Call(call) => {
+            if let Expr::Path(syn::ExprPath {
+                qself: None, path, ..
+            }) = &*call.func
+            {
+                let segments = &path.segments;
+                if segments.len() > 1 && segments.last().is_some_and(|s| s.ident == "start") {
+                    let type_segments = segments.iter().take(segments.len() - 1);
+                    let leading_colon = path.leading_colon;
+                    return Ok((
+                        child.clone(),
+                        Some(parse_quote!(#leading_colon #(#type_segments)::*)),
+                    ));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok((child, None))
+}
+
+/// looks for a `freeze!()` (or an `await_freezable!()`, or an `.await`) inside of the given code
+///
+/// closures and nested functions are not a part of our function's body,
+/// a `freeze!()` inside of them would not freeze our function
+#[derive(Default)]
+struct FreezeFinder {
+    found: Option<proc_macro2::Span>,
+    /// looks only for the `.await`s
+    dot_await_only: bool,
+}
+
+impl<'ast> Visit<'ast> for FreezeFinder {
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        if self.found.is_none() && !self.dot_await_only && (is_freeze(mac) || is_await(mac)) {
+            self.found = Some(mac.span());
+        }
+    }
+
+    fn visit_expr_await(&mut self, dot_await: &'ast syn::ExprAwait) {
+        if self.found.is_none() {
+            self.found = Some(dot_await.await_token.span);
+        }
+    }
+
+    fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+
+    fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+
+    fn visit_item(&mut self, _: &'ast syn::Item) {}
+}
+
+fn find_freeze(e: &Expr) -> Option<proc_macro2::Span> {
+    let mut finder = FreezeFinder::default();
+    finder.visit_expr(e);
+    finder.found
+}
+
+/// the first `.await` in the body of the function, they can only be used in an `async fn`
+pub fn find_dot_await(block: &syn::Block) -> Option<proc_macro2::Span> {
+    let mut finder = FreezeFinder {
+        found: None,
+        dot_await_only: true,
+    };
+    finder.visit_block(block);
+    finder.found
+}
+
+fn contains_freeze(e: &Expr) -> bool {
+    find_freeze(e).is_some()
+}
+
+/// a `freeze!()` in a place that we can't split into chunks, would silently do nothing
+fn check_no_freeze(e: &Expr) -> Result<(), syn::Error> {
+    match find_freeze(e) {
+        Some(span) => Err(syn::Error::new(
+            span,
+            "`freeze!()`, `await_freezable!()` and `.await` can only be used as statements, or inside of `if`/`else` branches and loops",
+        )),
+        None => Ok(()),
+    }
+}
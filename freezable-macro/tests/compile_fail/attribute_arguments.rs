@@ -0,0 +1,29 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable(fast)]
+fn with_arguments(begin: u8) -> u8 {
+    freeze!();
+    begin
+}
+
+#[freezable(name = "Two Words")]
+fn invalid_name(begin: u8) -> u8 {
+    freeze!();
+    begin
+}
+
+#[freezable(state_prefix = "0")]
+fn invalid_prefix(begin: u8) -> u8 {
+    freeze!();
+    begin
+}
+
+#[freezable(start = "begin", start = "new")]
+fn duplicated(begin: u8) -> u8 {
+    freeze!();
+    begin
+}
+
+fn main() {}
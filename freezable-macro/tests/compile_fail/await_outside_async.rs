@@ -0,0 +1,20 @@
+use freezable_macro::freezable;
+
+#[freezable]
+fn child(begin: u8) -> u8 {
+    freeze!();
+    begin
+}
+
+#[freezable]
+fn not_async(begin: u8) -> u8 {
+    child::start(begin).await
+}
+
+#[freezable]
+async fn await_in_expression(begin: u8) -> u8 {
+    let doubled: u8 = child::start(begin).await * 2;
+    doubled
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn break_with_value(begin: u8) -> u8 {
+    let mut next: u8 = begin;
+    loop {
+        next += 1;
+        freeze!(next);
+        if next > 10 {
+            break next;
+        }
+    }
+}
+
+fn main() {}
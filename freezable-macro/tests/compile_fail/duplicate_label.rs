@@ -0,0 +1,23 @@
+use freezable_macro::freezable;
+
+#[freezable]
+fn same_labels(begin: u8) -> u8 {
+    freeze!(@Waiting);
+    freeze!(@Waiting, begin);
+    begin
+}
+
+#[freezable]
+fn reserved_label(begin: u8) -> u8 {
+    freeze!(@Finished);
+    begin
+}
+
+#[freezable(state_prefix = "Step")]
+fn generated_label(begin: u8) -> u8 {
+    freeze!(@Step2);
+    freeze!();
+    begin
+}
+
+fn main() {}
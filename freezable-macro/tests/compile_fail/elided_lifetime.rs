@@ -0,0 +1,11 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn elided_lifetime(text: &str) -> usize {
+    freeze!();
+    text.len()
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+fn double(number: u8) -> u8 {
+    number * 2
+}
+
+#[freezable]
+fn freeze_in_expression(begin: u8) -> u8 {
+    double(freeze!(begin));
+    begin
+}
+
+fn main() {}
@@ -0,0 +1,24 @@
+use freezable_macro::generator;
+
+#[generator]
+fn no_yield_type(count: u8) {
+    yield_!(count);
+}
+
+#[generator(yield = 5)]
+fn yield_not_a_string(count: u8) {
+    yield_!(count);
+}
+
+#[generator(yield = "u8", fast)]
+fn unknown_argument(count: u8) {
+    yield_!(count);
+}
+
+#[generator(yield = "u8")]
+fn wrong_item_type(count: u8) {
+    yield_!("text");
+    yield_!(count);
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn if_let_freeze(begin: Option<u8>) -> u8 {
+    if let Some(number) = begin {
+        freeze!(number);
+    }
+    0
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn impl_trait(numbers: impl Iterator<Item = u8>) -> u8 {
+    freeze!();
+    numbers.sum()
+}
+
+fn main() {}
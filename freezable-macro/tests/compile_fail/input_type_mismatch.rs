@@ -0,0 +1,12 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn input_type_mismatch(begin: u8) -> String {
+    let x: u8 = freeze!(begin);
+    let s: String = freeze!(x);
+    s
+}
+
+fn main() {}
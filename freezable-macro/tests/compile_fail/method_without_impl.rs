@@ -0,0 +1,17 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+pub struct Counter {
+    count: u8,
+}
+
+impl Counter {
+    #[freezable]
+    fn count(&mut self) -> u8 {
+        freeze!();
+        self.count
+    }
+}
+
+fn main() {}
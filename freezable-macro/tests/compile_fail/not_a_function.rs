@@ -0,0 +1,6 @@
+use freezable_macro::freezable;
+
+#[freezable]
+struct NotAFunction;
+
+fn main() {}
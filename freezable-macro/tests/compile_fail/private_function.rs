@@ -0,0 +1,15 @@
+mod inner {
+    #[allow(unused_imports)]
+    use freezable::{freeze, Freezable, FreezableError, FreezableState};
+    use freezable_macro::freezable;
+
+    #[freezable]
+    fn private_function(begin: u8) -> u8 {
+        freeze!();
+        begin
+    }
+}
+
+fn main() {
+    let _ = inner::private_function::start(5);
+}
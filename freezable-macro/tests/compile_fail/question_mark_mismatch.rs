@@ -0,0 +1,12 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn option_in_result(values: Vec<u8>) -> Result<u8, String> {
+    let first: u8 = values.first().copied()?;
+    freeze!();
+    Ok(first)
+}
+
+fn main() {}
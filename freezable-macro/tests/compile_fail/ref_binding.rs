@@ -0,0 +1,12 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn ref_binding(text: String) -> usize {
+    let ref borrowed: String = text;
+    freeze!();
+    borrowed.len()
+}
+
+fn main() {}
@@ -0,0 +1,37 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn shadowed_in_branch(a: u8) -> u8 {
+    let x: u8 = a;
+    if a > 1 {
+        let x: String = "s".into();
+        freeze!();
+        drop(x);
+    }
+    x
+}
+
+#[freezable]
+fn shadowed_in_loop(n: u8) -> u8 {
+    let mut x: u8 = 0;
+    while x < n {
+        x += 1;
+        let x: u16 = 7;
+        freeze!();
+        let _y: u16 = x;
+    }
+    x
+}
+
+#[freezable]
+fn shadowed_by_element(n: u8) -> u8 {
+    let i: u8 = n;
+    for i in 0..n {
+        freeze!(i);
+    }
+    i
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+pub struct Point {
+    x: u8,
+    y: u8,
+}
+
+#[freezable]
+fn struct_pattern(point: Point) -> u8 {
+    let Point { x, y }: Point = point;
+    freeze!();
+    x + y
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use freezable_macro::freezable;
+
+pub struct Counter;
+
+#[freezable]
+impl Clone for Counter {
+    fn clone(&self) -> Self {
+        Counter
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn tuple_type_mismatch(begin: u8) -> u8 {
+    let (a, b): (u8, u8, u8) = (begin, begin, begin);
+    freeze!();
+    a + b
+}
+
+fn main() {}
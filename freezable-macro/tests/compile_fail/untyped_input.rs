@@ -0,0 +1,11 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn untyped_input(begin: u8) -> u8 {
+    let received = freeze!(begin);
+    received
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+#[allow(unused_imports)]
+use freezable::freeze;
+use freezable_macro::freezable;
+
+#[freezable]
+fn untyped_two_lifetimes<'a, 'b>(first: &'a str, second: &'b str) -> usize {
+    let total = first.len() + second.len();
+    freeze!();
+    total
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+#![deny(unused_must_use)]
+
+#[allow(unused_imports)]
+use freezable::{freeze, Freezable, FreezableError, FreezableState};
+use freezable_macro::freezable;
+
+#[freezable]
+#[must_use]
+fn unused_start(begin: u8) -> u8 {
+    freeze!();
+    begin
+}
+
+fn main() {
+    unused_start::start(5);
+}
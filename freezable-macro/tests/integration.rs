@@ -1,6 +1,9 @@
 #[allow(unused_imports)]
-use freezable::{freeze, Freezable, FreezableError, FreezableState};
-use freezable_macro::freezable;
+use freezable::{
+    await_freezable, freeze, yield_, Freezable, FreezableError, FreezableExt, FreezableState,
+    FreezableWith,
+};
+use freezable_macro::{freezable, generator};
 
 #[freezable]
 fn freezable_complex(begin: u8) -> String {
@@ -27,6 +30,579 @@ fn freezable_generator_4(begin: u8) -> u8 {
     next
 }
 
+#[freezable]
+fn freezable_conditional(begin: u8) -> u8 {
+    let mut next: u8 = begin;
+    if next < 5 {
+        next += 1;
+        freeze!(next); // freezes only for the small numbers
+        next += 1;
+    } else if next > 10 {
+        freeze!(next);
+    } else {
+        next *= 2;
+    }
+    freeze!(next);
+    next + 1
+}
+
+#[freezable]
+fn freezable_conditional_return(begin: u8) -> String {
+    let text: String = begin.to_string();
+    if begin > 5 {
+        freeze!();
+        text
+    } else {
+        "small".to_string()
+    }
+}
+
+#[freezable]
+fn freezable_loop(limit: u8) -> u8 {
+    let mut counter: u8 = 0;
+    loop {
+        counter += 1;
+        if counter > limit {
+            break;
+        }
+        freeze!(counter);
+    }
+    counter
+}
+
+#[freezable]
+fn freezable_while(begin: u8) -> u8 {
+    let mut next: u8 = begin;
+    while next < 10 {
+        next += 3;
+        if next == 7 {
+            continue;
+        }
+        freeze!(next);
+    }
+    next
+}
+
+#[freezable]
+fn freezable_for(end: u8) -> u8 {
+    let mut sum: u8 = 0;
+    for i in 1..end {
+        sum += i;
+        freeze!(sum);
+    }
+    sum
+}
+
+#[freezable]
+fn freezable_nested_loops(rows: u8, columns: u8) -> u8 {
+    let mut cells: u8 = 0;
+    'rows: for row in 0..rows {
+        let mut column: u8 = 0;
+        while column < columns {
+            if row == 2 {
+                break 'rows;
+            }
+            column += 1;
+            cells += 1;
+            freeze!(cells);
+        }
+    }
+    cells
+}
+
+#[freezable]
+fn freezable_expressions(begin: u8) -> usize {
+    let mut text: String = begin.to_string();
+    freeze!(text.len()); // borrows `text`, right before it is stored in the next state
+    text.push_str("00");
+    freeze!(text.len() * 2 + 1);
+    text.len()
+}
+
+#[freezable]
+fn freezable_full_types(rows: Vec<Vec<u8>>, title: std::string::String) -> usize {
+    let (count, (prefix, suffix)): (usize, (&'static str, [u8; 2])) =
+        (rows.len(), ("rows", [1, 2]));
+    freeze!(count);
+    let mut total: usize = title.len() + prefix.len() + suffix.len();
+    for row in rows {
+        total += row.len();
+        freeze!(total);
+    }
+    total
+}
+
+#[freezable]
+fn freezable_repeat<T>(item: T, times: usize) -> Vec<T>
+where
+    T: Clone,
+{
+    let mut items: Vec<T> = Vec::new();
+    while items.len() < times {
+        items.push(item.clone());
+        freeze!();
+    }
+    items
+}
+
+#[freezable]
+fn freezable_prefixes<'a, const N: usize>(text: &'a str) -> &'a str {
+    let mut end: usize = 0;
+    while end < N {
+        end += 1;
+        freeze!(&text[..end]);
+    }
+    &text[..N]
+}
+
+#[freezable]
+fn freezable_early_return(limit: u8) -> u8 {
+    let mut total: u8 = 0;
+    for step in 1..=limit {
+        if step == 3 {
+            freeze!(0);
+            return total * 10;
+        }
+        total += step;
+        freeze!(total);
+    }
+    if total == 0 {
+        return 42;
+    }
+    total
+}
+
+#[freezable]
+fn freezable_delegate(begin: u8) -> u8 {
+    let generated: u8 = await_freezable!(freezable_generator_4::start(begin));
+    freeze!(generated);
+    let mut total: u8 = generated;
+    for round in 1..=2u8 {
+        let counted = await_freezable!(freezable_loop::start(round));
+        total += counted;
+    }
+    await_freezable!(freezable_conditional::start(total))
+}
+
+/// the same as `freezable_delegate`, written as an `async fn`
+#[freezable]
+async fn freezable_async_delegate(begin: u8) -> u8 {
+    let generated: u8 = freezable_generator_4::start(begin).await;
+    freeze!(generated);
+    let mut total: u8 = generated;
+    for round in 1..=2u8 {
+        let counted = freezable_loop::start(round).await;
+        total += counted;
+    }
+    freezable_conditional::start(total).await
+}
+
+#[freezable]
+fn freezable_receiver(limit: u8) -> u8 {
+    let mut total: u8 = 0;
+    while total < limit {
+        let received: u8 = freeze!(total);
+        total += received;
+    }
+    total
+}
+
+#[freezable]
+fn freezable_buffer(seed: u8) -> u8 {
+    let buffer: [u8; 64] = [seed; 64];
+    let sum: u8 = buffer.iter().fold(0, |sum, byte| sum.wrapping_add(*byte));
+    freeze!(sum);
+    sum.wrapping_add(1)
+}
+
+/// the variables of the branch can be shadowed in the branch, and the outer ones after it
+#[freezable]
+fn freezable_shadowing(begin: u8) -> u8 {
+    let value: u8 = begin;
+    if value > 1 {
+        let inner: u8 = value * 2;
+        freeze!(inner);
+        let inner: u16 = inner as u16 + 1;
+        freeze!(inner as u8);
+    }
+    let value: u16 = value as u16 * 10;
+    freeze!();
+    value as u8
+}
+
+/// `sum` and `width` are only used in the format string, after the `freeze!()`
+#[freezable]
+fn freezable_format_arguments(seed: u8) -> String {
+    let sum: u8 = seed + 1;
+    let width: usize = 4;
+    freeze!();
+    format!("{sum:>width$}|{{sum}}")
+}
+
+#[freezable]
+fn freezable_untyped(begin: u8) -> String {
+    let next = begin + 1;
+    freeze!();
+    let (doubled, mut text) = (next * 2, String::from("value: "));
+    freeze!();
+    text.push_str(&doubled.to_string());
+    text
+}
+
+#[freezable]
+fn freezable_untyped_temporary(begin: u8) -> u8 {
+    // not stored, so the state machine is a normal enum
+    let doubled = begin * 2;
+    let next: u8 = doubled + 1;
+    freeze!(next);
+    next + 1
+}
+
+#[freezable]
+fn freezable_enumerate(words: Vec<String>) -> usize {
+    let mut total = 0;
+    for (i, word) in words.into_iter().enumerate() {
+        total += i * word.len();
+        freeze!(total);
+    }
+    total
+}
+
+fn generator(begin: u8) -> freezable_generator_4 {
+    freezable_generator_4::start(begin)
+}
+
+/// awaits two of them at the same time, and the first one of the other two
+#[freezable]
+async fn freezable_structured(begin: u8) -> u8 {
+    let (generated, counted) = freezable::join(
+        freezable_generator_4::start(begin),
+        freezable_loop::start(5),
+    )
+    .await;
+    let (index, fastest) = freezable::select!(
+        freezable_loop::start(10),
+        freezable_generator_4::start(generated),
+    )
+    .await;
+    generated + counted + index as u8 + fastest
+}
+
+#[freezable]
+fn freezable_untyped_delegate(begin: u8) -> u8 {
+    let generated = await_freezable!(generator(begin));
+    let received: u8 = freeze!(generated);
+    generated + received
+}
+
+#[freezable]
+fn freezable_collect<T: Clone + std::fmt::Debug>(value: T, count: u8) -> Vec<T> {
+    let mut items = Vec::new();
+    for _ in 0..count {
+        items.push(value.clone());
+        freeze!();
+    }
+    items
+}
+
+#[freezable(
+    name = "Countdown",
+    derive(Debug, Clone, PartialEq),
+    state_prefix = "Step",
+    start = "new"
+)]
+fn countdown(from: u8) -> u8 {
+    let mut current: u8 = from;
+    while current > 0 {
+        freeze!(current);
+        current -= 1;
+    }
+    current
+}
+
+/// no parameters, and nothing is stored in the variants
+#[freezable]
+fn freezable_no_parameters() -> u8 {
+    freeze!(1);
+    freeze!();
+    2
+}
+
+/// runs to completion in a single `unfreeze`
+#[freezable]
+fn freezable_no_freeze(begin: u8) -> u8 {
+    let doubled: u8 = begin * 2;
+    doubled + 1
+}
+
+/// a constant generator
+#[freezable]
+fn freezable_constant() -> &'static str {
+    "done"
+}
+
+/// a trivial task without an output
+#[freezable]
+fn freezable_empty() {}
+
+/// the type of the stored variable is inferred, without any parameters
+#[freezable]
+fn freezable_untyped_no_parameters() -> u8 {
+    let start = 4;
+    freeze!();
+    start + 1
+}
+
+/// the states are named after the labels of the `freeze!()`s
+#[freezable(derive(Debug, PartialEq))]
+fn handshake(greeting: u8) -> u8 {
+    freeze!(@Connecting);
+    let reply: u8 = freeze!(@AwaitingHandshake, greeting);
+    let total: u8 = greeting + reply;
+    freeze!(total);
+    total
+}
+
+/// the labels work in the boxed state machines as well
+#[freezable]
+fn handshake_untyped(greeting: u8) -> u8 {
+    let doubled = greeting * 2;
+    freeze!(@Doubled, doubled);
+    doubled + 1
+}
+
+#[generator(yield = "u8")]
+fn countdown_generator(from: u8) -> &'static str {
+    let mut current: u8 = from;
+    while current > 0 {
+        yield_!(current);
+        current -= 1;
+    }
+    "liftoff"
+}
+
+/// the awaited freezables and `freeze!()` don't yield anything
+#[generator(yield = "String", name = "Numbers", start = "new")]
+fn numbers(count: u8) -> usize {
+    let mut total = 0;
+    for round in 0..count {
+        let generated: u8 = await_freezable!(freezable_generator_4::start(round));
+        yield_!(generated.to_string());
+        total += 1;
+    }
+    freeze!();
+    total
+}
+
+#[generator(yield = "u32")]
+fn parse_all(inputs: Vec<&'static str>) -> Result<(), std::num::ParseIntError> {
+    for input in inputs {
+        let value: u32 = input.parse()?;
+        yield_!(value);
+    }
+    Ok(())
+}
+
+#[generator(yield = "T")]
+fn repeat_generator<T: Clone + std::fmt::Debug>(item: T, times: usize) {
+    for _ in 0..times {
+        yield_!(item.clone());
+    }
+}
+
+/// nothing is imported here, and the names of the `freezable` crate are taken by other items
+mod hygiene {
+    use freezable_macro::freezable;
+
+    #[allow(dead_code)]
+    pub struct FreezableState;
+    #[allow(dead_code)]
+    pub trait Freezable {}
+
+    /// a crate that re-exports `freezable`
+    pub mod reexport {
+        pub use ::freezable as inner;
+    }
+
+    #[freezable]
+    pub fn no_imports(begin: u8) -> u8 {
+        freeze!(begin);
+        begin + 1
+    }
+
+    #[freezable(crate = "crate::hygiene::reexport::inner")]
+    pub fn reexported(begin: u8) -> u8 {
+        let received: u8 = freeze!(begin);
+        let doubled = received * 2;
+        freeze!();
+        doubled
+    }
+}
+
+/// not used anywhere, the attributes are put on the generated items
+#[freezable]
+#[allow(dead_code)]
+#[must_use]
+fn freezable_unused(begin: u8) -> u8 {
+    freeze!();
+    begin
+}
+
+// would not compile, if the items were generated without the `#[cfg]`
+#[freezable]
+#[cfg(any())]
+fn freezable_disabled(begin: u8) -> NotAType {
+    freeze!();
+    begin
+}
+
+#[derive(Debug, PartialEq)]
+enum SumError {
+    Parse(std::num::ParseIntError),
+    TooLarge,
+}
+
+impl From<std::num::ParseIntError> for SumError {
+    fn from(error: std::num::ParseIntError) -> Self {
+        SumError::Parse(error)
+    }
+}
+
+fn check_limit(sum: u32) -> Result<(), SumError> {
+    if sum > 100 {
+        Err(SumError::TooLarge)
+    } else {
+        Ok(())
+    }
+}
+
+#[freezable]
+fn freezable_parse_sum(inputs: Vec<&'static str>) -> Result<u32, SumError> {
+    let mut sum: u32 = 0;
+    for input in inputs {
+        let number: u32 = input.parse()?;
+        sum += number;
+        freeze!(Ok(sum));
+    }
+    check_limit(sum)?;
+    Ok(sum)
+}
+
+/// the slice is `Copy`, so it can be used after the loop
+#[freezable]
+fn freezable_slice_sum<'a>(values: &'a [u8]) -> usize {
+    let mut sum: usize = 0;
+    for value in values {
+        sum += *value as usize;
+        freeze!(sum);
+    }
+    sum + values.len()
+}
+
+/// `?` on an `Option` finishes the function with `None`
+#[freezable]
+fn freezable_first_words(text: &'static str) -> Option<usize> {
+    let mut words = text.split_whitespace();
+    let first: &'static str = words.next()?;
+    freeze!(Some(first.len()));
+    let second: &'static str = words.next()?;
+    Some(first.len() + second.len())
+}
+
+#[derive(Debug)]
+struct Counter {
+    count: u32,
+}
+
+#[freezable]
+impl Counter {
+    fn new() -> Self {
+        Counter { count: 0 }
+    }
+
+    #[freezable]
+    fn count_to<'a>(&'a mut self, limit: u32) -> u32 {
+        while self.count < limit {
+            self.count += 1;
+            freeze!(self.count);
+        }
+        self.count
+    }
+
+    #[freezable]
+    fn reset(&mut self, step: u32) -> u32 {
+        self.count += step;
+        freeze!(self.count);
+        *self = Self::new();
+        self.count
+    }
+
+    #[freezable]
+    fn peek(&self) -> u32 {
+        self.count
+    }
+
+    #[freezable]
+    fn into_count(self) -> u32 {
+        freeze!();
+        self.count
+    }
+
+    #[freezable]
+    async fn add_counted(&mut self, round: u8) -> u32 {
+        let counted: u8 = freezable_loop::start(round).await;
+        self.count += counted as u32;
+        self.count
+    }
+
+    #[freezable(name = "CounterDouble", derive(Debug), start = "begin")]
+    fn double(&mut self) -> u32 {
+        self.count *= 2;
+        freeze!();
+        self.count
+    }
+}
+
+struct Stack<T> {
+    items: Vec<T>,
+}
+
+#[freezable]
+impl<T> Stack<T>
+where
+    T: std::fmt::Debug,
+{
+    #[freezable]
+    fn drain(&mut self) -> usize {
+        let mut drained: usize = 0;
+        while !self.items.is_empty() {
+            self.items.pop();
+            drained += 1;
+            freeze!(drained);
+        }
+        drained
+    }
+
+    #[freezable]
+    fn take_all(&mut self) -> Vec<T> {
+        freeze!();
+        let taken: Self = std::mem::replace(self, Self { items: Vec::new() });
+        taken.items
+    }
+}
+
+struct Slot<T>(Option<T>);
+
+#[freezable]
+impl<T: std::fmt::Debug> Slot<T> {
+    #[freezable]
+    fn put(&mut self, value: T) -> Option<T> {
+        freeze!();
+        let Self(previous) = std::mem::replace(self, Self(Some(value)));
+        previous
+    }
+}
+
 #[test]
 fn complex_cancel_test() {
     let mut complex_5 = freezable_complex::start(5);
@@ -155,3 +731,719 @@ fn generator_unfreeze_after_cancel_test() {
     assert_eq!(generator_5.unfreeze(), Err(FreezableError::Cancelled));
     assert_eq!(generator_5.unfreeze(), Err(FreezableError::Cancelled));
 }
+
+#[test]
+fn conditional_then_branch_test() {
+    let mut conditional_4 = freezable_conditional::start(4);
+    assert_eq!(
+        conditional_4.unfreeze(),
+        Ok(FreezableState::Frozen(Some(5)))
+    );
+    assert_eq!(
+        conditional_4.unfreeze(),
+        Ok(FreezableState::Frozen(Some(6)))
+    );
+    assert_eq!(conditional_4.unfreeze(), Ok(FreezableState::Finished(7)));
+    assert_eq!(
+        conditional_4.unfreeze(),
+        Err(FreezableError::AlreadyFinished)
+    );
+}
+
+#[test]
+fn conditional_else_if_branch_test() {
+    let mut conditional_11 = freezable_conditional::start(11);
+    assert_eq!(
+        conditional_11.unfreeze(),
+        Ok(FreezableState::Frozen(Some(11)))
+    );
+    assert_eq!(
+        conditional_11.unfreeze(),
+        Ok(FreezableState::Frozen(Some(11)))
+    );
+    assert_eq!(conditional_11.unfreeze(), Ok(FreezableState::Finished(12)));
+}
+
+#[test]
+fn conditional_else_branch_test() {
+    let mut conditional_7 = freezable_conditional::start(7);
+    assert_eq!(
+        conditional_7.unfreeze(),
+        Ok(FreezableState::Frozen(Some(14)))
+    );
+    assert_eq!(conditional_7.unfreeze(), Ok(FreezableState::Finished(15)));
+    assert!(conditional_7.is_finished());
+}
+
+#[test]
+fn conditional_return_test() {
+    let mut conditional_7 = freezable_conditional_return::start(7);
+    assert_eq!(conditional_7.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(
+        conditional_7.unfreeze(),
+        Ok(FreezableState::Finished("7".to_string()))
+    );
+
+    let mut conditional_2 = freezable_conditional_return::start(2);
+    assert_eq!(
+        conditional_2.unfreeze(),
+        Ok(FreezableState::Finished("small".to_string()))
+    );
+}
+
+#[test]
+fn loop_test() {
+    let mut loop_3 = freezable_loop::start(3);
+    assert_eq!(loop_3.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(loop_3.unfreeze(), Ok(FreezableState::Frozen(Some(2))));
+    assert_eq!(loop_3.unfreeze(), Ok(FreezableState::Frozen(Some(3))));
+    assert_eq!(loop_3.unfreeze(), Ok(FreezableState::Finished(4)));
+    assert_eq!(loop_3.unfreeze(), Err(FreezableError::AlreadyFinished));
+}
+
+#[test]
+fn while_test() {
+    let mut while_1 = freezable_while::start(1);
+    assert_eq!(while_1.unfreeze(), Ok(FreezableState::Frozen(Some(4))));
+    // 7 is skipped via `continue`
+    assert_eq!(while_1.unfreeze(), Ok(FreezableState::Frozen(Some(10))));
+    assert_eq!(while_1.unfreeze(), Ok(FreezableState::Finished(10)));
+
+    let mut while_20 = freezable_while::start(20);
+    assert_eq!(while_20.unfreeze(), Ok(FreezableState::Finished(20)));
+}
+
+#[test]
+fn for_test() {
+    let mut for_4 = freezable_for::start(4);
+    assert_eq!(for_4.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(for_4.unfreeze(), Ok(FreezableState::Frozen(Some(3))));
+    assert_eq!(for_4.unfreeze(), Ok(FreezableState::Frozen(Some(6))));
+    assert_eq!(for_4.unfreeze(), Ok(FreezableState::Finished(6)));
+}
+
+#[test]
+fn nested_loops_test() {
+    let mut nested_loops = freezable_nested_loops::start(3, 2);
+    for cells in 1..=4 {
+        assert_eq!(
+            nested_loops.unfreeze(),
+            Ok(FreezableState::Frozen(Some(cells)))
+        );
+    }
+    // the third row breaks out of both loops
+    assert_eq!(nested_loops.unfreeze(), Ok(FreezableState::Finished(4)));
+}
+
+#[test]
+fn loop_cancel_test() {
+    let mut loop_10 = freezable_loop::start(10);
+    assert_eq!(loop_10.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    loop_10.cancel();
+    assert!(loop_10.is_cancelled());
+    assert_eq!(loop_10.unfreeze(), Err(FreezableError::Cancelled));
+}
+
+#[test]
+fn expression_in_freeze_test() {
+    let mut expressions_7 = freezable_expressions::start(7);
+    assert_eq!(
+        expressions_7.unfreeze(),
+        Ok(FreezableState::Frozen(Some(1)))
+    );
+    assert_eq!(
+        expressions_7.unfreeze(),
+        Ok(FreezableState::Frozen(Some(7)))
+    );
+    assert_eq!(expressions_7.unfreeze(), Ok(FreezableState::Finished(3)));
+}
+
+#[test]
+fn full_types_test() {
+    let rows = vec![vec![1, 2], vec![3]];
+    let mut full_types = freezable_full_types::start(rows, "ab".to_string());
+    assert_eq!(full_types.unfreeze(), Ok(FreezableState::Frozen(Some(2))));
+    assert_eq!(full_types.unfreeze(), Ok(FreezableState::Frozen(Some(10))));
+    assert_eq!(full_types.unfreeze(), Ok(FreezableState::Frozen(Some(11))));
+    assert_eq!(full_types.unfreeze(), Ok(FreezableState::Finished(11)));
+}
+
+#[test]
+fn generic_type_test() {
+    let mut repeat = freezable_repeat::start("ab", 2);
+    assert_eq!(repeat.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(repeat.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(
+        repeat.unfreeze(),
+        Ok(FreezableState::Finished(vec!["ab", "ab"]))
+    );
+}
+
+#[test]
+fn lifetime_and_const_generic_test() {
+    let text = String::from("hello");
+    let mut prefixes = freezable_prefixes::<3>::start(&text);
+    assert_eq!(prefixes.unfreeze(), Ok(FreezableState::Frozen(Some("h"))));
+    assert_eq!(prefixes.unfreeze(), Ok(FreezableState::Frozen(Some("he"))));
+    assert_eq!(prefixes.unfreeze(), Ok(FreezableState::Frozen(Some("hel"))));
+    assert_eq!(prefixes.unfreeze(), Ok(FreezableState::Finished("hel")));
+}
+
+#[test]
+fn method_test() {
+    let mut counter = Counter::new();
+    let mut count_to = counter.count_to_start(2);
+    assert_eq!(count_to.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(count_to.unfreeze(), Ok(FreezableState::Frozen(Some(2))));
+    assert_eq!(count_to.unfreeze(), Ok(FreezableState::Finished(2)));
+    assert_eq!(counter.count, 2);
+
+    let mut reset = Counter::reset_start(&mut counter, 3);
+    assert_eq!(reset.unfreeze(), Ok(FreezableState::Frozen(Some(5))));
+    assert_eq!(reset.unfreeze(), Ok(FreezableState::Finished(0)));
+
+    counter.count = 7;
+    let mut into_count = counter.into_count_start();
+    assert_eq!(into_count.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(into_count.unfreeze(), Ok(FreezableState::Finished(7)));
+
+    let mut counter = Counter::new();
+    let mut add_counted = counter.add_counted_start(1);
+    // the loop freezes once, and finishes with 2
+    assert_eq!(add_counted.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(add_counted.unfreeze(), Ok(FreezableState::Finished(2)));
+    assert_eq!(counter.count, 2);
+}
+
+#[test]
+fn generic_self_constructor_test() {
+    let mut stack = Stack { items: vec![1, 2] };
+    let mut take_all = stack.take_all_start();
+    assert_eq!(take_all.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(
+        take_all.unfreeze(),
+        Ok(FreezableState::Finished(vec![1, 2]))
+    );
+    assert!(stack.items.is_empty());
+
+    let mut slot = Slot(Some("old"));
+    let mut put = slot.put_start("new");
+    assert_eq!(put.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(put.unfreeze(), Ok(FreezableState::Finished(Some("old"))));
+    assert_eq!(slot.0, Some("new"));
+}
+
+#[test]
+fn arguments_test() {
+    let mut countdown = Countdown::new(2);
+    assert_eq!(countdown, Countdown::Step0(2));
+    assert_eq!(countdown.unfreeze(), Ok(FreezableState::Frozen(Some(2))));
+    let copy = countdown.clone();
+    assert_eq!(countdown.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(countdown.unfreeze(), Ok(FreezableState::Finished(0)));
+    assert_eq!(countdown, Countdown::Finished);
+    assert_eq!(format!("{:?}", countdown), "Finished");
+    assert_ne!(copy, countdown);
+
+    let mut counter = Counter::new();
+    counter.count = 3;
+    let mut double: CounterDouble = counter.double_start();
+    assert!(format!("{:?}", double).starts_with("Chunk0"));
+    assert_eq!(double.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(double.unfreeze(), Ok(FreezableState::Finished(6)));
+    let _ = CounterDouble::begin(&mut counter);
+}
+
+#[test]
+fn trivial_functions_test() {
+    let mut no_parameters = freezable_no_parameters::start();
+    assert_eq!(
+        no_parameters.unfreeze(),
+        Ok(FreezableState::Frozen(Some(1)))
+    );
+    assert_eq!(no_parameters.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(no_parameters.unfreeze(), Ok(FreezableState::Finished(2)));
+
+    let mut no_freeze = freezable_no_freeze::start(3);
+    assert_eq!(no_freeze.state_count(), 3);
+    assert_eq!(no_freeze.unfreeze(), Ok(FreezableState::Finished(7)));
+    assert_eq!(no_freeze.unfreeze(), Err(FreezableError::AlreadyFinished));
+
+    let mut constant = freezable_constant::start();
+    assert_eq!(constant.unfreeze(), Ok(FreezableState::Finished("done")));
+    assert!(constant.is_finished());
+
+    let mut empty = freezable_empty::start();
+    assert_eq!(format!("{:?}", empty.debug_state()), "Chunk0");
+    assert_eq!(empty.unfreeze(), Ok(FreezableState::Finished(())));
+
+    let mut cancelled = freezable_empty::start();
+    cancelled.cancel();
+    assert_eq!(cancelled.unfreeze(), Err(FreezableError::Cancelled));
+
+    let mut untyped = freezable_untyped_no_parameters::start();
+    assert_eq!(untyped.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(untyped.unfreeze(), Ok(FreezableState::Finished(5)));
+
+    let mut counter = Counter::new();
+    counter.count = 2;
+    let mut peek = counter.peek_start();
+    assert_eq!(peek.unfreeze(), Ok(FreezableState::Finished(2)));
+}
+
+#[test]
+fn state_name_test() {
+    let mut handshake = handshake::start(2);
+    assert_eq!(handshake.state_name(), "Chunk0");
+    assert_eq!(handshake.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(handshake, handshake::Connecting(2));
+    assert_eq!(handshake.state_name(), "Connecting");
+    assert_eq!(handshake.unfreeze(), Ok(FreezableState::Frozen(Some(2))));
+    assert_eq!(handshake.state_name(), "AwaitingHandshake");
+    assert_eq!(
+        handshake.unfreeze_with(3),
+        Ok(FreezableState::Frozen(Some(5)))
+    );
+    assert_eq!(handshake.state_name(), "Chunk3");
+    assert_eq!(handshake.unfreeze(), Ok(FreezableState::Finished(5)));
+    assert_eq!(handshake.state_name(), "Finished");
+
+    let mut cancelled = handshake::start(2);
+    cancelled.cancel();
+    assert_eq!(cancelled.state_name(), "Cancelled");
+
+    let mut untyped = handshake_untyped::start(3);
+    assert_eq!(untyped.unfreeze(), Ok(FreezableState::Frozen(Some(6))));
+    assert_eq!(untyped.state_name(), "Doubled");
+    assert_eq!(untyped.unfreeze(), Ok(FreezableState::Finished(7)));
+    assert_eq!(untyped.state_name(), "Finished");
+
+    // the hand-written state machines name their states too
+    let mut generator = freezable::FreezableGenerator4::start(1);
+    assert_eq!(generator.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(generator.state_name(), "Chunk1");
+}
+
+/// doesn't implement `Debug`
+struct Opaque(u8);
+
+#[freezable]
+fn freezable_opaque(count: u8) -> u8 {
+    let opaque: Opaque = Opaque(count);
+    freeze!();
+    count + opaque.0
+}
+
+#[test]
+fn state_introspection_test() {
+    let mut complex = freezable_complex::start(5);
+    assert_eq!((complex.state_index(), complex.state_count()), (0, 6));
+    assert_eq!(
+        format!("{:?}", complex.debug_state()),
+        "Chunk0 { begin: 5 }"
+    );
+    let _ = complex.unfreeze();
+    let _ = complex.unfreeze();
+    assert_eq!(complex.state_index(), 2);
+    assert_eq!(
+        format!("{:?}", complex.debug_state()),
+        "Chunk2 { num1: 6, num2: 4 }"
+    );
+    let _ = complex.unfreeze();
+    let _ = complex.unfreeze();
+    assert_eq!(complex.state_index(), 4);
+    assert_eq!(format!("{:?}", complex.debug_state()), "Finished");
+    complex.cancel();
+    assert_eq!(complex.state_index(), 5);
+
+    // the iterator of the loop is not written
+    let mut sum = freezable_for::start(3);
+    let _ = sum.unfreeze();
+    assert_eq!(format!("{:?}", sum.debug_state()), "Chunk4 { sum: 1 }");
+
+    // the variables that don't implement `Debug` are written as `_`
+    let mut opaque = freezable_opaque::start(2);
+    let _ = opaque.unfreeze();
+    assert_eq!(
+        format!("{:?}", opaque.debug_state()),
+        "Chunk1 { count: 2, opaque: _ }"
+    );
+
+    // the labels are the names of the states, and the boxed state machines are described as well
+    let mut untyped = handshake_untyped::start(3);
+    let _ = untyped.unfreeze();
+    assert_eq!((untyped.state_index(), untyped.state_count()), (1, 4));
+    assert_eq!(
+        format!("{:?}", untyped.debug_state()),
+        "Doubled { doubled: _ }"
+    );
+
+    // the hand-written state machines too
+    let mut generator = freezable::FreezableGenerator4::start(1);
+    let _ = generator.unfreeze();
+    assert_eq!(
+        format!("{:?}", generator.debug_state()),
+        "Chunk1 { next: 1 }"
+    );
+    let tasks: Vec<Box<dyn Freezable<Output = u8>>> = vec![Box::new(generator)];
+    assert_eq!(
+        format!("{:?}", freezable::DebugState(&*tasks[0])),
+        "Chunk1 { next: 1 }"
+    );
+}
+
+#[test]
+fn generator_test() {
+    let mut countdown = countdown_generator::start(3);
+    assert_eq!(countdown.return_value(), None);
+    assert_eq!(countdown.by_ref().collect::<Vec<u8>>(), vec![3, 2, 1]);
+    assert_eq!(countdown.return_value(), Some(&"liftoff"));
+    assert_eq!(countdown.next(), None);
+    assert!(countdown.freezable().is_finished());
+
+    let mut numbers = Numbers::new(3);
+    assert_eq!(
+        numbers.by_ref().collect::<Vec<String>>(),
+        vec!["3", "4", "5"]
+    );
+    assert_eq!(numbers.into_return_value(), Some(3));
+
+    // the error of `?` is the return value
+    let mut parsed = parse_all::start(vec!["1", "2", "x", "4"]);
+    assert_eq!(parsed.by_ref().sum::<u32>(), 3);
+    assert!(parsed.return_value().unwrap().is_err());
+    let mut parsed = parse_all::start(vec!["1", "2"]);
+    assert_eq!(parsed.by_ref().count(), 2);
+    assert_eq!(parsed.return_value(), Some(&Ok(())));
+
+    let words: Vec<&str> = repeat_generator::start("hey", 2).collect();
+    assert_eq!(words, vec!["hey", "hey"]);
+
+    // the freezable can still be used directly
+    let mut generator = countdown_generator::start(1);
+    let freezable = generator.freezable();
+    assert_eq!(
+        freezable.unfreeze(),
+        Ok(FreezableState::Frozen(Some(
+            freezable::GeneratorState::Yielded(1)
+        )))
+    );
+    assert_eq!(
+        freezable.unfreeze(),
+        Ok(FreezableState::Finished(
+            freezable::GeneratorState::Complete("liftoff")
+        ))
+    );
+}
+
+#[test]
+fn partials_test() {
+    let doubled: Vec<u8> = freezable_for::start(4)
+        .into_iter_partials()
+        .map(|partial| partial * 2)
+        .collect();
+    assert_eq!(doubled, vec![2, 6, 12]);
+
+    // the freezes without a value are skipped
+    let mut partials = freezable_delegate::start(5).into_iter_partials();
+    assert_eq!(partials.by_ref().collect::<Vec<u8>>(), vec![8]);
+    assert_eq!(partials.into_output(), Some(14));
+}
+
+#[test]
+fn combinators_test() {
+    // the output of the loop is the limit of the next one
+    let mut task = freezable_loop::start(2)
+        .then(freezable_loop::start)
+        .map(|count| count * 10);
+    let mut steps = 0;
+    while !task.is_finished() {
+        let _ = task.unfreeze();
+        steps += 1;
+    }
+    assert!(steps > 2);
+
+    let pairs: Vec<(u8, String)> = freezable_for::start(4)
+        .zip(freezable_generator_4::start(1).map(|value| value.to_string()))
+        .into_iter_partials()
+        .collect();
+    assert_eq!(
+        pairs,
+        vec![
+            (1, "1".to_string()),
+            (3, "2".to_string()),
+            (6, "3".to_string())
+        ]
+    );
+
+    let mut limited = freezable_for::start(100)
+        .chain(freezable_for::start(3))
+        .take_states(3);
+    assert_eq!(limited.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    let _ = limited.unfreeze();
+    let _ = limited.unfreeze();
+    assert_eq!(limited.unfreeze(), Err(FreezableError::Cancelled));
+}
+
+#[test]
+fn hygiene_test() {
+    let mut no_imports = hygiene::no_imports::start(1);
+    assert_eq!(no_imports.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(no_imports.unfreeze(), Ok(FreezableState::Finished(2)));
+
+    let mut reexported = hygiene::reexported::start(1);
+    assert_eq!(reexported.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(
+        reexported.unfreeze_with(3),
+        Ok(FreezableState::Frozen(None))
+    );
+    assert_eq!(reexported.unfreeze(), Ok(FreezableState::Finished(6)));
+}
+
+#[test]
+fn generic_method_test() {
+    let mut stack = Stack {
+        items: vec!['a', 'b'],
+    };
+    let mut drain = stack.drain_start();
+    assert_eq!(drain.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(drain.unfreeze(), Ok(FreezableState::Frozen(Some(2))));
+    assert_eq!(drain.unfreeze(), Ok(FreezableState::Finished(2)));
+    assert!(stack.items.is_empty());
+}
+
+#[test]
+fn early_return_test() {
+    let mut return_2 = freezable_early_return::start(2);
+    assert_eq!(return_2.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(return_2.unfreeze(), Ok(FreezableState::Frozen(Some(3))));
+    assert_eq!(return_2.unfreeze(), Ok(FreezableState::Finished(3)));
+
+    let mut return_5 = freezable_early_return::start(5);
+    assert_eq!(return_5.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(return_5.unfreeze(), Ok(FreezableState::Frozen(Some(3))));
+    assert_eq!(return_5.unfreeze(), Ok(FreezableState::Frozen(Some(0))));
+    assert_eq!(return_5.unfreeze(), Ok(FreezableState::Finished(30)));
+    assert!(return_5.is_finished());
+
+    let mut return_0 = freezable_early_return::start(0);
+    assert_eq!(return_0.unfreeze(), Ok(FreezableState::Finished(42)));
+    assert_eq!(return_0.unfreeze(), Err(FreezableError::AlreadyFinished));
+}
+
+#[test]
+fn question_mark_test() {
+    let mut parse_sum = freezable_parse_sum::start(vec!["1", "2"]);
+    assert_eq!(
+        parse_sum.unfreeze(),
+        Ok(FreezableState::Frozen(Some(Ok(1))))
+    );
+    assert_eq!(
+        parse_sum.unfreeze(),
+        Ok(FreezableState::Frozen(Some(Ok(3))))
+    );
+    assert_eq!(parse_sum.unfreeze(), Ok(FreezableState::Finished(Ok(3))));
+
+    let mut parse_error = freezable_parse_sum::start(vec!["1", "x", "2"]);
+    let error = "x".parse::<u32>().unwrap_err();
+    assert_eq!(
+        parse_error.unfreeze(),
+        Ok(FreezableState::Frozen(Some(Ok(1))))
+    );
+    assert_eq!(
+        parse_error.unfreeze(),
+        Ok(FreezableState::Finished(Err(SumError::Parse(error))))
+    );
+    assert!(parse_error.is_finished());
+
+    let mut too_large = freezable_parse_sum::start(vec!["101"]);
+    assert_eq!(
+        too_large.unfreeze(),
+        Ok(FreezableState::Frozen(Some(Ok(101))))
+    );
+    assert_eq!(
+        too_large.unfreeze(),
+        Ok(FreezableState::Finished(Err(SumError::TooLarge)))
+    );
+}
+
+#[test]
+fn copy_iterable_test() {
+    let values = [1, 2];
+    let mut sum = freezable_slice_sum::start(&values);
+    assert_eq!(sum.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(sum.unfreeze(), Ok(FreezableState::Frozen(Some(3))));
+    assert_eq!(sum.unfreeze(), Ok(FreezableState::Finished(5)));
+}
+
+#[test]
+fn question_mark_option_test() {
+    let mut words = freezable_first_words::start("ab cde");
+    assert_eq!(words.unfreeze(), Ok(FreezableState::Frozen(Some(Some(2)))));
+    assert_eq!(words.unfreeze(), Ok(FreezableState::Finished(Some(5))));
+
+    let mut one_word = freezable_first_words::start("ab");
+    assert_eq!(
+        one_word.unfreeze(),
+        Ok(FreezableState::Frozen(Some(Some(2))))
+    );
+    assert_eq!(one_word.unfreeze(), Ok(FreezableState::Finished(None)));
+
+    let mut empty = freezable_first_words::start("");
+    assert_eq!(empty.unfreeze(), Ok(FreezableState::Finished(None)));
+}
+
+#[test]
+fn await_test() {
+    let mut delegate = freezable_delegate::start(5);
+    // the child freezes 3 times with 5, 6, 7, and finishes with 8
+    for _ in 0..3 {
+        assert_eq!(delegate.unfreeze(), Ok(FreezableState::Frozen(None)));
+    }
+    assert_eq!(delegate.unfreeze(), Ok(FreezableState::Frozen(Some(8))));
+    // the loops of the rounds freeze 1 and 2 times, then the conditional freezes 2 times
+    for _ in 0..5 {
+        assert_eq!(delegate.unfreeze(), Ok(FreezableState::Frozen(None)));
+    }
+    assert_eq!(delegate.unfreeze(), Ok(FreezableState::Finished(14)));
+    assert_eq!(delegate.unfreeze(), Err(FreezableError::AlreadyFinished));
+}
+
+#[test]
+fn async_fn_test() {
+    let mut delegate = freezable_delegate::start(5);
+    let mut async_delegate = freezable_async_delegate::start(5);
+    loop {
+        let state = async_delegate.unfreeze();
+        assert_eq!(state, delegate.unfreeze());
+        if let Ok(FreezableState::Finished(output)) = state {
+            assert_eq!(output, 14);
+            break;
+        }
+    }
+    assert_eq!(async_delegate.state_name(), "Finished");
+}
+
+#[test]
+fn join_and_select_test() {
+    let mut structured = freezable_structured::start(1);
+    let mut steps = 0;
+    let output = loop {
+        steps += 1;
+        if let Ok(FreezableState::Finished(output)) = structured.unfreeze() {
+            break output;
+        }
+    };
+    // the join waits for the loop (6 steps), the select for the generator (4 steps),
+    // the select starts in the step the join is finished
+    assert_eq!(steps, 6 + 4 - 1);
+    assert_eq!(output, 4 + 6 + 1 + 7);
+}
+
+#[test]
+fn unfreeze_with_test() {
+    let mut receiver = freezable_receiver::start(5);
+    // nothing is waiting for the first input
+    assert_eq!(
+        receiver.unfreeze_with(100),
+        Ok(FreezableState::Frozen(Some(0)))
+    );
+    assert_eq!(
+        receiver.unfreeze_with(2),
+        Ok(FreezableState::Frozen(Some(2)))
+    );
+    assert_eq!(receiver.unfreeze(), Err(FreezableError::MissingInput));
+    assert_eq!(receiver.unfreeze_with(4), Ok(FreezableState::Finished(6)));
+    assert_eq!(
+        receiver.unfreeze_with(1),
+        Err(FreezableError::AlreadyFinished)
+    );
+}
+
+#[test]
+fn dead_variables_test() {
+    // the buffer is not used after the `freeze!()`, so it is not stored in the variant
+    assert!(std::mem::size_of::<freezable_buffer>() < 64);
+    let mut buffer = freezable_buffer::start(2);
+    assert_eq!(buffer.unfreeze(), Ok(FreezableState::Frozen(Some(128))));
+    assert_eq!(buffer.unfreeze(), Ok(FreezableState::Finished(129)));
+}
+
+#[test]
+fn shadowing_test() {
+    let mut shadowing = freezable_shadowing::start(2);
+    assert_eq!(shadowing.unfreeze(), Ok(FreezableState::Frozen(Some(4))));
+    assert_eq!(shadowing.unfreeze(), Ok(FreezableState::Frozen(Some(5))));
+    assert_eq!(shadowing.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(shadowing.unfreeze(), Ok(FreezableState::Finished(20)));
+}
+
+#[test]
+fn format_arguments_test() {
+    let mut formatted = freezable_format_arguments::start(2);
+    assert_eq!(formatted.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(
+        formatted.unfreeze(),
+        Ok(FreezableState::Finished("   3|{sum}".to_string()))
+    );
+}
+
+#[test]
+fn unwrapped_variables_test() {
+    // the variants store the `u8` directly, next to the discriminant
+    assert_eq!(std::mem::size_of::<freezable_generator_4>(), 2);
+}
+
+#[test]
+fn inferred_types_test() {
+    let mut untyped = freezable_untyped::start(4);
+    assert_eq!(untyped.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(untyped.unfreeze(), Ok(FreezableState::Frozen(None)));
+    untyped.cancel();
+    assert!(untyped.is_cancelled());
+
+    let mut untyped = freezable_untyped::start(4);
+    let mut states = vec![];
+    while !untyped.is_finished() {
+        states.push(untyped.unfreeze());
+    }
+    assert_eq!(
+        states.pop(),
+        Some(Ok(FreezableState::Finished("value: 10".to_string())))
+    );
+
+    // the temporary is not stored, so nothing is inferred or boxed
+    assert_eq!(std::mem::size_of::<freezable_untyped_temporary>(), 2);
+    let mut temporary = freezable_untyped_temporary::start(3);
+    assert_eq!(temporary.unfreeze(), Ok(FreezableState::Frozen(Some(7))));
+    assert_eq!(temporary.unfreeze(), Ok(FreezableState::Finished(8)));
+}
+
+#[test]
+fn inferred_iterator_test() {
+    let words = vec!["a".to_string(), "bb".to_string(), "ccc".to_string()];
+    let mut enumerate = freezable_enumerate::start(words);
+    assert_eq!(enumerate.unfreeze(), Ok(FreezableState::Frozen(Some(0))));
+    assert_eq!(enumerate.unfreeze(), Ok(FreezableState::Frozen(Some(2))));
+    assert_eq!(enumerate.unfreeze(), Ok(FreezableState::Frozen(Some(8))));
+    assert_eq!(enumerate.unfreeze(), Ok(FreezableState::Finished(8)));
+
+    let mut collect = freezable_collect::start('x', 2);
+    assert_eq!(collect.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(collect.unfreeze(), Ok(FreezableState::Frozen(None)));
+    assert_eq!(
+        collect.unfreeze(),
+        Ok(FreezableState::Finished(vec!['x', 'x']))
+    );
+}
+
+#[test]
+fn inferred_await_test() {
+    let mut delegate = freezable_untyped_delegate::start(1);
+    for _ in 0..3 {
+        assert_eq!(delegate.unfreeze(), Ok(FreezableState::Frozen(None)));
+    }
+    assert_eq!(delegate.unfreeze(), Ok(FreezableState::Frozen(Some(4))));
+    assert_eq!(delegate.unfreeze(), Err(FreezableError::MissingInput));
+    assert_eq!(delegate.unfreeze_with(10), Ok(FreezableState::Finished(14)));
+}
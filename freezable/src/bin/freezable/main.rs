@@ -45,6 +45,15 @@ fn complex_example() {
     call_cancel_randomly(&mut complex_10);
 }
 
+fn partials_example() {
+    println!("- iterating over the partial results");
+    let mut partials = FreezableGenerator4::start(5).into_iter_partials();
+    for partial in partials.by_ref() {
+        println!("partial result: {partial}");
+    }
+    println!("the output: {:?}", partials.output());
+}
+
 fn main() {
     println!("RUNNING THE GENERATOR EXAMPLE:");
     generator_example();
@@ -53,4 +62,9 @@ fn main() {
 
     println!("RUNNING THE COMPLEX EXAMPLE:");
     complex_example();
+
+    println!();
+
+    println!("RUNNING THE PARTIALS EXAMPLE:");
+    partials_example();
 }
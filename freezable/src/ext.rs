@@ -0,0 +1,573 @@
+//! Combinators for composing freezables, without writing a new state machine by hand
+//!
+//! ```ignore
+//! let task = FreezableGenerator4::start(1)
+//!     .map(|value| value * 10)                                   // 10, 20, 30, finishes with 40
+//!     .chain(FreezableGenerator4::start(5).map(|value| value * 10)) // then 50, 60, 70, finishes with 80
+//!     .take_states(5);                                           // cancelled after 5 steps
+//! ```
+//!
+//! every combinator is a freezable itself, so they can be given to the runtime, or combined again
+
+use crate::{Freezable, FreezableError, FreezableState};
+use std::fmt::{Debug, Formatter};
+
+/// the combinators of the freezables, implemented for every `Freezable`
+pub trait FreezableExt: Freezable + Sized {
+    /// applies `map` to the partial results and to the output
+    fn map<U, M>(self, map: M) -> Map<Self, M>
+    where
+        M: FnMut(Self::Output) -> U,
+        U: Debug,
+    {
+        Map {
+            freezable: self,
+            map,
+        }
+    }
+
+    /// runs `self`, then the freezable that is created from its output by `then`.
+    /// The partial results of `self` are dropped, since they are not of the same type
+    fn then<B, G>(self, then: G) -> Then<Self, B, G>
+    where
+        B: Freezable,
+        G: FnOnce(Self::Output) -> B,
+    {
+        Then {
+            first: self,
+            then: Some(then),
+            second: None,
+        }
+    }
+
+    /// runs `self`, then `next`. The output of `self` becomes a partial result
+    fn chain<B>(self, next: B) -> Chain<Self, B>
+    where
+        B: Freezable<Output = Self::Output>,
+    {
+        Chain {
+            first: self,
+            second: next,
+            first_finished: false,
+        }
+    }
+
+    /// runs `self` and `other` side by side, every `unfreeze` unfreezes both of them (until they are finished).
+    /// The partial results are given in pairs, when both of them give one in the same step.
+    /// Finishes with both of the outputs, when both of them are finished
+    fn zip<B>(self, other: B) -> Zip<Self, B>
+    where
+        B: Freezable,
+    {
+        Zip {
+            first: self,
+            second: other,
+            first_output: None,
+            second_output: None,
+        }
+    }
+
+    /// allows only `count` calls to `unfreeze`, the freezable is cancelled if it is not finished by then
+    fn take_states(self, count: usize) -> TakeStates<Self> {
+        TakeStates {
+            freezable: self,
+            remaining: count,
+        }
+    }
+}
+
+impl<F: Freezable> FreezableExt for F {}
+
+/// see `FreezableExt::map`
+pub struct Map<F, M> {
+    freezable: F,
+    map: M,
+}
+
+impl<F, M, U> Freezable for Map<F, M>
+where
+    F: Freezable,
+    M: FnMut(F::Output) -> U,
+    U: Debug,
+{
+    type Output = U;
+
+    fn unfreeze(&mut self) -> Result<FreezableState<U>, FreezableError> {
+        Ok(match self.freezable.unfreeze()? {
+            FreezableState::Frozen(partial) => FreezableState::Frozen(partial.map(&mut self.map)),
+            FreezableState::Finished(output) => FreezableState::Finished((self.map)(output)),
+        })
+    }
+
+    fn cancel(&mut self) {
+        self.freezable.cancel()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.freezable.is_cancelled()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.freezable.is_finished()
+    }
+
+    fn state_name(&self) -> &'static str {
+        self.freezable.state_name()
+    }
+
+    fn state_index(&self) -> usize {
+        self.freezable.state_index()
+    }
+
+    fn state_count(&self) -> usize {
+        self.freezable.state_count()
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.freezable.fmt_state(f)
+    }
+}
+
+/// see `FreezableExt::then`
+pub struct Then<A, B, G> {
+    first: A,
+    then: Option<G>,
+    second: Option<B>,
+}
+
+impl<A, B, G> Freezable for Then<A, B, G>
+where
+    A: Freezable,
+    B: Freezable,
+    G: FnOnce(A::Output) -> B,
+{
+    type Output = B::Output;
+
+    fn unfreeze(&mut self) -> Result<FreezableState<B::Output>, FreezableError> {
+        if let Some(second) = &mut self.second {
+            return second.unfreeze();
+        }
+        match self.first.unfreeze()? {
+            FreezableState::Frozen(_) => Ok(FreezableState::Frozen(None)),
+            // continues with the second one in the same call, like `await_freezable!()`
+            FreezableState::Finished(output) => {
+                let then = self
+                    .then
+                    .take()
+                    .expect("the first one is finished only once");
+                self.second.insert(then(output)).unfreeze()
+            }
+        }
+    }
+
+    fn cancel(&mut self) {
+        match &mut self.second {
+            Some(second) => second.cancel(),
+            None => self.first.cancel(),
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        match &self.second {
+            Some(second) => second.is_cancelled(),
+            None => self.first.is_cancelled(),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.second
+            .as_ref()
+            .is_some_and(|second| second.is_finished())
+    }
+
+    fn state_name(&self) -> &'static str {
+        match &self.second {
+            Some(second) => second.state_name(),
+            None => self.first.state_name(),
+        }
+    }
+
+    fn state_index(&self) -> usize {
+        match &self.second {
+            Some(second) => second.state_index(),
+            None => self.first.state_index(),
+        }
+    }
+
+    fn state_count(&self) -> usize {
+        match &self.second {
+            Some(second) => second.state_count(),
+            None => self.first.state_count(),
+        }
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        match &self.second {
+            Some(second) => second.fmt_state(f),
+            None => self.first.fmt_state(f),
+        }
+    }
+}
+
+/// see `FreezableExt::chain`
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+    first_finished: bool,
+}
+
+impl<A, B> Freezable for Chain<A, B>
+where
+    A: Freezable,
+    B: Freezable<Output = A::Output>,
+{
+    type Output = A::Output;
+
+    fn unfreeze(&mut self) -> Result<FreezableState<A::Output>, FreezableError> {
+        if self.first_finished {
+            return self.second.unfreeze();
+        }
+        Ok(match self.first.unfreeze()? {
+            FreezableState::Frozen(partial) => FreezableState::Frozen(partial),
+            FreezableState::Finished(output) => {
+                self.first_finished = true;
+                FreezableState::Frozen(Some(output))
+            }
+        })
+    }
+
+    fn cancel(&mut self) {
+        // the finished one would lose its state
+        if !self.first_finished {
+            self.first.cancel();
+        }
+        self.second.cancel();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.first.is_cancelled() || self.second.is_cancelled()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.first_finished && self.second.is_finished()
+    }
+
+    fn state_name(&self) -> &'static str {
+        if self.first_finished {
+            self.second.state_name()
+        } else {
+            self.first.state_name()
+        }
+    }
+
+    fn state_index(&self) -> usize {
+        if self.first_finished {
+            self.second.state_index()
+        } else {
+            self.first.state_index()
+        }
+    }
+
+    fn state_count(&self) -> usize {
+        if self.first_finished {
+            self.second.state_count()
+        } else {
+            self.first.state_count()
+        }
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        if self.first_finished {
+            self.second.fmt_state(f)
+        } else {
+            self.first.fmt_state(f)
+        }
+    }
+}
+
+/// see `FreezableExt::zip`
+pub struct Zip<A: Freezable, B: Freezable> {
+    first: A,
+    second: B,
+    /// the output of the one that is finished first, until the other one is finished
+    first_output: Option<A::Output>,
+    second_output: Option<B::Output>,
+}
+
+impl<A, B> Freezable for Zip<A, B>
+where
+    A: Freezable,
+    B: Freezable,
+{
+    type Output = (A::Output, B::Output);
+
+    fn unfreeze(&mut self) -> Result<FreezableState<Self::Output>, FreezableError> {
+        let mut first_partial = None;
+        if self.first_output.is_none() {
+            match self.first.unfreeze()? {
+                FreezableState::Frozen(partial) => first_partial = partial,
+                FreezableState::Finished(output) => self.first_output = Some(output),
+            }
+        }
+        let mut second_partial = None;
+        if self.second_output.is_none() {
+            match self.second.unfreeze()? {
+                FreezableState::Frozen(partial) => second_partial = partial,
+                FreezableState::Finished(output) => self.second_output = Some(output),
+            }
+        }
+
+        if self.first_output.is_some() && self.second_output.is_some() {
+            let outputs = (self.first_output.take(), self.second_output.take());
+            if let (Some(first), Some(second)) = outputs {
+                return Ok(FreezableState::Finished((first, second)));
+            }
+        }
+        Ok(FreezableState::Frozen(first_partial.zip(second_partial)))
+    }
+
+    fn cancel(&mut self) {
+        // the finished ones would lose their state
+        if !self.first.is_finished() {
+            self.first.cancel();
+        }
+        if !self.second.is_finished() {
+            self.second.cancel();
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.first.is_cancelled() || self.second.is_cancelled()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.first.is_finished() && self.second.is_finished()
+    }
+
+    // the state of the first one until it is finished, then the state of the second one
+    fn state_name(&self) -> &'static str {
+        if self.first.is_finished() {
+            self.second.state_name()
+        } else {
+            self.first.state_name()
+        }
+    }
+
+    fn state_index(&self) -> usize {
+        if self.first.is_finished() {
+            self.second.state_index()
+        } else {
+            self.first.state_index()
+        }
+    }
+
+    fn state_count(&self) -> usize {
+        if self.first.is_finished() {
+            self.second.state_count()
+        } else {
+            self.first.state_count()
+        }
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        if self.first.is_finished() {
+            self.second.fmt_state(f)
+        } else {
+            self.first.fmt_state(f)
+        }
+    }
+}
+
+/// see `FreezableExt::take_states`
+pub struct TakeStates<F> {
+    freezable: F,
+    remaining: usize,
+}
+
+impl<F: Freezable> Freezable for TakeStates<F> {
+    type Output = F::Output;
+
+    fn unfreeze(&mut self) -> Result<FreezableState<F::Output>, FreezableError> {
+        if self.remaining == 0 {
+            if !self.freezable.is_finished() {
+                self.freezable.cancel();
+            }
+        } else {
+            self.remaining -= 1;
+        }
+        self.freezable.unfreeze()
+    }
+
+    fn cancel(&mut self) {
+        self.freezable.cancel()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.freezable.is_cancelled()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.freezable.is_finished()
+    }
+
+    fn state_name(&self) -> &'static str {
+        self.freezable.state_name()
+    }
+
+    fn state_index(&self) -> usize {
+        self.freezable.state_index()
+    }
+
+    fn state_count(&self) -> usize {
+        self.freezable.state_count()
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.freezable.fmt_state(f)
+    }
+}
+
+#[test]
+fn map_test() {
+    use crate::FreezableGenerator4;
+
+    let mut mapped = FreezableGenerator4::start(1).map(|value| value.to_string());
+    assert_eq!(
+        mapped.unfreeze(),
+        Ok(FreezableState::Frozen(Some("1".to_string())))
+    );
+    assert_eq!(mapped.state_name(), "Chunk1");
+    let _ = mapped.unfreeze();
+    let _ = mapped.unfreeze();
+    assert_eq!(
+        mapped.unfreeze(),
+        Ok(FreezableState::Finished("4".to_string()))
+    );
+    assert!(mapped.is_finished());
+}
+
+#[test]
+fn then_test() {
+    use crate::{FreezableComplex, FreezableGenerator4};
+
+    // the partial results of the first one are dropped
+    let mut then = FreezableGenerator4::start(1).then(FreezableComplex::start);
+    for _ in 0..3 {
+        assert_eq!(then.unfreeze(), Ok(FreezableState::Frozen(None)));
+    }
+    // the first one finishes with 4, and the second one freezes in the same call
+    assert_eq!(then.unfreeze(), Ok(FreezableState::Frozen(None)));
+    let _ = then.unfreeze();
+    let _ = then.unfreeze();
+    assert_eq!(
+        then.unfreeze(),
+        Ok(FreezableState::Finished("15 a rando".to_string()))
+    );
+    assert!(then.is_finished());
+}
+
+#[test]
+fn chain_test() {
+    use crate::FreezableGenerator4;
+
+    let chain = FreezableGenerator4::start(1).chain(FreezableGenerator4::start(10));
+    let mut partials = chain.into_iter_partials();
+    assert_eq!(
+        partials.by_ref().collect::<Vec<u8>>(),
+        vec![1, 2, 3, 4, 10, 11, 12]
+    );
+    assert_eq!(partials.output(), Some(&13));
+
+    // the first one is cancelled, so is the chain
+    let mut cancelled = FreezableGenerator4::start(1)
+        .take_states(1)
+        .chain(FreezableGenerator4::start(10));
+    assert_eq!(cancelled.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(cancelled.unfreeze(), Err(FreezableError::Cancelled));
+    assert!(cancelled.is_cancelled());
+    assert!(!cancelled.is_finished());
+}
+
+#[test]
+fn combined_states_test() {
+    use crate::{FreezableComplex, FreezableGenerator4};
+
+    // the state of the one that is running
+    let second = FreezableComplex::start(5).map(|text| text.len() as u8);
+    let mut chain = FreezableGenerator4::start(1).chain(second);
+    let _ = chain.unfreeze();
+    assert_eq!(chain.state_name(), "Chunk1");
+    assert_eq!((chain.state_index(), chain.state_count()), (1, 6));
+    assert_eq!(format!("{:?}", chain.debug_state()), "Chunk1 { next: 1 }");
+    for _ in 0..4 {
+        let _ = chain.unfreeze();
+    }
+    assert_eq!(
+        format!("{:?}", chain.debug_state()),
+        "Chunk1 { current_num: 5 }"
+    );
+
+    let mut then = FreezableGenerator4::start(1).then(FreezableComplex::start);
+    let _ = then.unfreeze();
+    assert_eq!(format!("{:?}", then.debug_state()), "Chunk1 { next: 1 }");
+    for _ in 0..3 {
+        let _ = then.unfreeze();
+    }
+    assert_eq!(
+        format!("{:?}", then.debug_state()),
+        "Chunk1 { current_num: 4 }"
+    );
+
+    let mut zip = FreezableGenerator4::start(1).zip(FreezableComplex::start(5).take_states(10));
+    let _ = zip.unfreeze();
+    assert_eq!(format!("{:?}", zip.debug_state()), "Chunk1 { next: 1 }");
+    for _ in 0..3 {
+        let _ = zip.unfreeze();
+    }
+    assert_eq!(zip.state_name(), "Finished");
+    // the finished ones are not cancelled
+    zip.cancel();
+    assert_eq!(zip.state_name(), "Finished");
+    assert!(!zip.is_cancelled());
+}
+
+#[test]
+fn zip_test() {
+    use crate::{FreezableComplex, FreezableGenerator4};
+
+    let mut zip = FreezableGenerator4::start(1).zip(FreezableGenerator4::start(10));
+    assert_eq!(zip.unfreeze(), Ok(FreezableState::Frozen(Some((1, 10)))));
+    assert_eq!(zip.unfreeze(), Ok(FreezableState::Frozen(Some((2, 11)))));
+    assert_eq!(zip.unfreeze(), Ok(FreezableState::Frozen(Some((3, 12)))));
+    assert_eq!(zip.unfreeze(), Ok(FreezableState::Finished((4, 13))));
+    assert_eq!(zip.unfreeze(), Err(FreezableError::AlreadyFinished));
+
+    // the partial results are given only in pairs, the output of the shorter one waits for the other one
+    let mut zip = FreezableGenerator4::start(1).zip(FreezableComplex::start(5).take_states(10));
+    for _ in 0..3 {
+        assert_eq!(zip.unfreeze(), Ok(FreezableState::Frozen(None)));
+    }
+    assert_eq!(
+        zip.unfreeze(),
+        Ok(FreezableState::Finished((4, "24 a rando".to_string())))
+    );
+}
+
+#[test]
+fn take_states_test() {
+    use crate::FreezableGenerator4;
+
+    let mut limited = FreezableGenerator4::start(1).take_states(2);
+    assert_eq!(limited.unfreeze(), Ok(FreezableState::Frozen(Some(1))));
+    assert_eq!(limited.unfreeze(), Ok(FreezableState::Frozen(Some(2))));
+    assert_eq!(limited.unfreeze(), Err(FreezableError::Cancelled));
+    assert!(limited.is_cancelled());
+
+    // the finished one is not cancelled
+    let mut enough = FreezableGenerator4::start(1).take_states(4);
+    for _ in 0..4 {
+        let _ = enough.unfreeze();
+    }
+    assert_eq!(enough.unfreeze(), Err(FreezableError::AlreadyFinished));
+    assert!(!enough.is_cancelled());
+}
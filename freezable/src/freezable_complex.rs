@@ -24,6 +24,7 @@
 //! See below for the desugared version of the above code
 
 use crate::{Freezable, FreezableError, FreezableState};
+use std::fmt::Formatter;
 
 /// State Machine for our Freezable that will run 3 chunks of code
 /// first state is for initial state
@@ -93,6 +94,55 @@ impl Freezable for FreezableComplex {
     fn is_finished(&self) -> bool {
         matches!(self, FreezableComplex::Finished)
     }
+
+    fn state_name(&self) -> &'static str {
+        match self {
+            FreezableComplex::Chunk0(..) => "Chunk0",
+            FreezableComplex::Chunk1(..) => "Chunk1",
+            FreezableComplex::Chunk2(..) => "Chunk2",
+            FreezableComplex::Chunk3(..) => "Chunk3",
+            FreezableComplex::Finished => "Finished",
+            FreezableComplex::Cancelled => "Cancelled",
+        }
+    }
+
+    fn state_index(&self) -> usize {
+        match self {
+            FreezableComplex::Chunk0(..) => 0,
+            FreezableComplex::Chunk1(..) => 1,
+            FreezableComplex::Chunk2(..) => 2,
+            FreezableComplex::Chunk3(..) => 3,
+            FreezableComplex::Finished => 4,
+            FreezableComplex::Cancelled => 5,
+        }
+    }
+
+    fn state_count(&self) -> usize {
+        6
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            FreezableComplex::Chunk0(begin) => {
+                f.debug_struct("Chunk0").field("begin", begin).finish()
+            }
+            FreezableComplex::Chunk1(current_num) => f
+                .debug_struct("Chunk1")
+                .field("current_num", current_num)
+                .finish(),
+            FreezableComplex::Chunk2(num1, num2) => f
+                .debug_struct("Chunk2")
+                .field("num1", num1)
+                .field("num2", num2)
+                .finish(),
+            FreezableComplex::Chunk3(mult_str) => f
+                .debug_struct("Chunk3")
+                .field("mult_str", mult_str)
+                .finish(),
+            FreezableComplex::Finished => f.write_str("Finished"),
+            FreezableComplex::Cancelled => f.write_str("Cancelled"),
+        }
+    }
 }
 
 #[test]
@@ -23,6 +23,7 @@
 //! See below for the desugared version of the above code
 
 use crate::{Freezable, FreezableError, FreezableState};
+use std::fmt::Formatter;
 
 /// State Machine for our Freezable that will generate 4 numbers
 /// first 4 states are for generating 4 numbers
@@ -88,6 +89,51 @@ impl Freezable for FreezableGenerator4 {
     fn is_finished(&self) -> bool {
         matches!(self, FreezableGenerator4::Finished)
     }
+
+    fn state_name(&self) -> &'static str {
+        match self {
+            FreezableGenerator4::Chunk0(..) => "Chunk0",
+            FreezableGenerator4::Chunk1(..) => "Chunk1",
+            FreezableGenerator4::Chunk2(..) => "Chunk2",
+            FreezableGenerator4::Chunk3(..) => "Chunk3",
+            FreezableGenerator4::Finished => "Finished",
+            FreezableGenerator4::Cancelled => "Cancelled",
+        }
+    }
+
+    fn state_index(&self) -> usize {
+        match self {
+            FreezableGenerator4::Chunk0(..) => 0,
+            FreezableGenerator4::Chunk1(..) => 1,
+            FreezableGenerator4::Chunk2(..) => 2,
+            FreezableGenerator4::Chunk3(..) => 3,
+            FreezableGenerator4::Finished => 4,
+            FreezableGenerator4::Cancelled => 5,
+        }
+    }
+
+    fn state_count(&self) -> usize {
+        6
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            FreezableGenerator4::Chunk0(begin) => {
+                f.debug_struct("Chunk0").field("begin", begin).finish()
+            }
+            FreezableGenerator4::Chunk1(next) => {
+                f.debug_struct("Chunk1").field("next", next).finish()
+            }
+            FreezableGenerator4::Chunk2(next) => {
+                f.debug_struct("Chunk2").field("next", next).finish()
+            }
+            FreezableGenerator4::Chunk3(next) => {
+                f.debug_struct("Chunk3").field("next", next).finish()
+            }
+            FreezableGenerator4::Finished => f.write_str("Finished"),
+            FreezableGenerator4::Cancelled => f.write_str("Cancelled"),
+        }
+    }
 }
 
 #[test]
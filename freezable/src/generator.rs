@@ -0,0 +1,94 @@
+//! Generators: freezables that yield items, and return a value at the end
+//!
+//! Original Code:
+//! ```ignore
+//! #[generator(yield = "u8")]
+//! fn countdown(from: u8) -> &'static str {
+//!     let mut current: u8 = from;
+//!     while current > 0 {
+//!         yield_!(current);  // an item of the iterator
+//!         current -= 1;
+//!     }
+//!     "liftoff"  // the return value, after the last item
+//! }
+//!
+//! let mut countdown = countdown::start(3);
+//! assert_eq!(countdown.by_ref().collect::<Vec<u8>>(), vec![3, 2, 1]);
+//! assert_eq!(countdown.return_value(), Some(&"liftoff"));
+//! ```
+//!
+//! the function is a freezable with the output `GeneratorState<u8, &'static str>`: every `yield_!(x)`
+//! is a `freeze!(GeneratorState::Yielded(x))`, and the return value is `GeneratorState::Complete(value)`.
+//! `Generator` unfreezes it for every item.
+
+use crate::{Freezable, FreezableState};
+use std::marker::PhantomData;
+
+/// the output of a generator, which is an item or the return value
+#[derive(Debug, PartialEq, Eq)]
+pub enum GeneratorState<Y, R> {
+    Yielded(Y),
+    Complete(R),
+}
+
+/// `Iterator` over the items that are yielded by a freezable, the return value is kept when it is finished
+///
+/// the freezes without an item (`freeze!()`, or a frozen freezable that is awaited) are skipped.
+/// The iteration ends when the freezable is finished, or if it is cancelled
+pub struct Generator<F, Y, R> {
+    freezable: F,
+    return_value: Option<R>,
+    _yields: PhantomData<fn() -> Y>,
+}
+
+impl<F, Y, R> Generator<F, Y, R>
+where
+    F: Freezable<Output = GeneratorState<Y, R>>,
+{
+    pub fn new(freezable: F) -> Self {
+        Generator {
+            freezable,
+            return_value: None,
+            _yields: PhantomData,
+        }
+    }
+
+    /// the value that is returned by the generator, after the last item
+    pub fn return_value(&self) -> Option<&R> {
+        self.return_value.as_ref()
+    }
+
+    pub fn into_return_value(self) -> Option<R> {
+        self.return_value
+    }
+
+    /// the freezable that is iterated, to check or cancel it
+    pub fn freezable(&mut self) -> &mut F {
+        &mut self.freezable
+    }
+}
+
+impl<F, Y, R> Iterator for Generator<F, Y, R>
+where
+    F: Freezable<Output = GeneratorState<Y, R>>,
+{
+    type Item = Y;
+
+    fn next(&mut self) -> Option<Y> {
+        loop {
+            match self.freezable.unfreeze() {
+                Ok(FreezableState::Frozen(Some(GeneratorState::Yielded(item)))) => {
+                    return Some(item)
+                }
+                Ok(FreezableState::Frozen(_)) => continue,
+                Ok(FreezableState::Finished(GeneratorState::Complete(value))) => {
+                    self.return_value = Some(value);
+                    return None;
+                }
+                // the last item is given as the output
+                Ok(FreezableState::Finished(GeneratorState::Yielded(item))) => return Some(item),
+                Err(_) => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! An `Iterator` over the partial results of any Freezable
+//!
+//! ```ignore
+//! let mut partials = FreezableGenerator4::start(5).into_iter_partials();
+//! let doubled: Vec<u8> = partials.by_ref().map(|partial| partial * 2).collect(); // [10, 12, 14]
+//! assert_eq!(partials.output(), Some(&8));
+//! ```
+//!
+//! instead of calling `unfreeze` in a loop, and matching on the states by hand
+
+use crate::{Freezable, FreezableState};
+use std::iter::FusedIterator;
+
+/// yields the value of every `Frozen(Some(value))`, the freezes without a value are skipped.
+/// The iteration ends when the Freezable is finished (the output is kept), or when `unfreeze` fails
+pub struct FreezableIter<F: Freezable> {
+    freezable: F,
+    output: Option<F::Output>,
+}
+
+impl<F: Freezable> FreezableIter<F> {
+    pub fn new(freezable: F) -> Self {
+        FreezableIter {
+            freezable,
+            output: None,
+        }
+    }
+
+    /// the output of the `Finished` state, after the last partial result
+    pub fn output(&self) -> Option<&F::Output> {
+        self.output.as_ref()
+    }
+
+    pub fn into_output(self) -> Option<F::Output> {
+        self.output
+    }
+
+    /// the freezable that is iterated, to check or cancel it
+    pub fn freezable(&mut self) -> &mut F {
+        &mut self.freezable
+    }
+}
+
+impl<F: Freezable> Iterator for FreezableIter<F> {
+    type Item = F::Output;
+
+    fn next(&mut self) -> Option<F::Output> {
+        loop {
+            match self.freezable.unfreeze() {
+                Ok(FreezableState::Frozen(Some(partial))) => return Some(partial),
+                Ok(FreezableState::Frozen(None)) => continue,
+                Ok(FreezableState::Finished(output)) => {
+                    self.output = Some(output);
+                    return None;
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+// a finished or cancelled freezable returns an error from then on
+impl<F: Freezable> FusedIterator for FreezableIter<F> {}
+
+#[test]
+fn partials_test() {
+    use crate::FreezableGenerator4;
+
+    let mut partials = FreezableGenerator4::start(5).into_iter_partials();
+    assert_eq!(partials.output(), None);
+    assert_eq!(partials.by_ref().collect::<Vec<u8>>(), vec![5, 6, 7]);
+    assert_eq!(partials.output(), Some(&8));
+    assert_eq!(partials.next(), None);
+    assert_eq!(partials.into_output(), Some(8));
+}
+
+#[test]
+fn partials_without_values_test() {
+    use crate::FreezableComplex;
+
+    // freezes 3 times without a value
+    let mut partials = FreezableComplex::start(5).into_iter_partials();
+    assert_eq!(partials.next(), None);
+    assert_eq!(partials.into_output(), Some(String::from("24 a rando")));
+}
+
+#[test]
+fn partials_cancel_test() {
+    use crate::FreezableGenerator4;
+
+    let mut partials = FreezableGenerator4::start(5).into_iter_partials();
+    assert_eq!(partials.next(), Some(5));
+    partials.freezable().cancel();
+    assert_eq!(partials.next(), None);
+    assert_eq!(partials.output(), None);
+}
@@ -0,0 +1,570 @@
+//! Running freezables side by side: `join` waits for all of them, `select` for the first one
+//!
+//! ```ignore
+//! // finishes with (4, 13), when both of them are finished
+//! let both = freezable::join(FreezableGenerator4::start(1), FreezableGenerator4::start(10));
+//!
+//! // finishes with `Selected::First(4)`, the other one is cancelled
+//! let first = freezable::select(FreezableGenerator4::start(1), FreezableComplex::start(5));
+//!
+//! // with more of them: `join!(a, b, c)` finishes with (a, b, c), and `select!(a, b, c)` with
+//! // (the index of the first one that is finished, its output)
+//! ```
+//!
+//! every `unfreeze` unfreezes all of the freezables that are not finished yet, in the order they are given.
+//! The partial results are dropped, since there can be more than one of them in a step.
+//! They are freezables themselves, so they can be given to the runtime, or awaited in a freezable function
+
+use crate::{Freezable, FreezableError, FreezableExt, FreezableState, Zip};
+use std::fmt::Formatter;
+
+/// runs `first` and `second` side by side, finishes with both of the outputs when both of them are finished
+pub fn join<A: Freezable, B: Freezable>(first: A, second: B) -> Join<A, B> {
+    Join {
+        zip: first.zip(second),
+    }
+}
+
+/// runs `first` and `second` side by side, finishes with the output of the one that is finished first,
+/// and the other one is cancelled. `first` is unfrozen first, so it wins if both of them finish in the same step
+pub fn select<A: Freezable, B: Freezable>(first: A, second: B) -> Select<A, B> {
+    Select {
+        first,
+        second,
+        finished: false,
+    }
+}
+
+/// `join` for any number of freezables of the same type, finishes with the outputs in the same order
+pub fn join_all<F: Freezable>(freezables: Vec<F>) -> JoinAll<F> {
+    let outputs = freezables.iter().map(|_| None).collect();
+    JoinAll {
+        freezables,
+        outputs,
+        finished: false,
+    }
+}
+
+/// `select` for any number of freezables of the same type,
+/// finishes with the index of the one that is finished first and its output
+///
+/// `None` if there are no freezables, since there wouldn't be an output
+pub fn select_all<F: Freezable>(freezables: Vec<F>) -> Option<SelectAll<F>> {
+    if freezables.is_empty() {
+        return None;
+    }
+    Some(SelectAll {
+        freezables,
+        finished: false,
+    })
+}
+
+/// see `join`, the same as `Zip` without the partial results
+pub struct Join<A: Freezable, B: Freezable> {
+    zip: Zip<A, B>,
+}
+
+impl<A, B> Freezable for Join<A, B>
+where
+    A: Freezable,
+    B: Freezable,
+{
+    type Output = (A::Output, B::Output);
+
+    fn unfreeze(&mut self) -> Result<FreezableState<Self::Output>, FreezableError> {
+        Ok(match self.zip.unfreeze()? {
+            FreezableState::Frozen(_) => FreezableState::Frozen(None),
+            FreezableState::Finished(outputs) => FreezableState::Finished(outputs),
+        })
+    }
+
+    fn cancel(&mut self) {
+        self.zip.cancel()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.zip.is_cancelled()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.zip.is_finished()
+    }
+
+    fn state_name(&self) -> &'static str {
+        self.zip.state_name()
+    }
+
+    fn state_index(&self) -> usize {
+        self.zip.state_index()
+    }
+
+    fn state_count(&self) -> usize {
+        self.zip.state_count()
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.zip.fmt_state(f)
+    }
+}
+
+/// the output of `select`: which one of them is finished first, and its output
+#[derive(Debug, PartialEq, Eq)]
+pub enum Selected<A, B> {
+    First(A),
+    Second(B),
+}
+
+/// see `select`
+pub struct Select<A, B> {
+    first: A,
+    second: B,
+    finished: bool,
+}
+
+impl<A, B> Freezable for Select<A, B>
+where
+    A: Freezable,
+    B: Freezable,
+{
+    type Output = Selected<A::Output, B::Output>;
+
+    fn unfreeze(&mut self) -> Result<FreezableState<Self::Output>, FreezableError> {
+        if self.finished {
+            return Err(FreezableError::AlreadyFinished);
+        }
+        if let FreezableState::Finished(output) = self.first.unfreeze()? {
+            self.finished = true;
+            self.second.cancel();
+            return Ok(FreezableState::Finished(Selected::First(output)));
+        }
+        if let FreezableState::Finished(output) = self.second.unfreeze()? {
+            self.finished = true;
+            self.first.cancel();
+            return Ok(FreezableState::Finished(Selected::Second(output)));
+        }
+        Ok(FreezableState::Frozen(None))
+    }
+
+    fn cancel(&mut self) {
+        if !self.finished {
+            self.first.cancel();
+            self.second.cancel();
+        }
+    }
+
+    // the one that lost is cancelled, but the select itself is finished
+    fn is_cancelled(&self) -> bool {
+        !self.finished && (self.first.is_cancelled() || self.second.is_cancelled())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // the state of the first one, or of the second one if it is the one that is finished
+    fn state_name(&self) -> &'static str {
+        if self.second.is_finished() {
+            self.second.state_name()
+        } else {
+            self.first.state_name()
+        }
+    }
+
+    fn state_index(&self) -> usize {
+        if self.second.is_finished() {
+            self.second.state_index()
+        } else {
+            self.first.state_index()
+        }
+    }
+
+    fn state_count(&self) -> usize {
+        if self.second.is_finished() {
+            self.second.state_count()
+        } else {
+            self.first.state_count()
+        }
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        if self.second.is_finished() {
+            self.second.fmt_state(f)
+        } else {
+            self.first.fmt_state(f)
+        }
+    }
+}
+
+/// see `join_all`
+pub struct JoinAll<F: Freezable> {
+    freezables: Vec<F>,
+    /// the outputs of the ones that are finished, in the same order as the freezables
+    outputs: Vec<Option<F::Output>>,
+    finished: bool,
+}
+
+impl<F: Freezable> Freezable for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn unfreeze(&mut self) -> Result<FreezableState<Vec<F::Output>>, FreezableError> {
+        if self.finished {
+            return Err(FreezableError::AlreadyFinished);
+        }
+        for (freezable, output) in self.freezables.iter_mut().zip(self.outputs.iter_mut()) {
+            if output.is_none() {
+                if let FreezableState::Finished(value) = freezable.unfreeze()? {
+                    *output = Some(value);
+                }
+            }
+        }
+
+        if self.outputs.iter().all(Option::is_some) {
+            self.finished = true;
+            let outputs = self.outputs.drain(..).flatten().collect();
+            return Ok(FreezableState::Finished(outputs));
+        }
+        Ok(FreezableState::Frozen(None))
+    }
+
+    fn cancel(&mut self) {
+        if !self.finished {
+            self.freezables
+                .iter_mut()
+                .for_each(|freezable| freezable.cancel());
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        !self.finished
+            && self
+                .freezables
+                .iter()
+                .any(|freezable| freezable.is_cancelled())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn state_name(&self) -> &'static str {
+        match self.running() {
+            Some(freezable) => freezable.state_name(),
+            None if self.finished => "Finished",
+            None => "Frozen",
+        }
+    }
+
+    fn state_index(&self) -> usize {
+        match self.running() {
+            Some(freezable) => freezable.state_index(),
+            None => usize::from(self.finished),
+        }
+    }
+
+    fn state_count(&self) -> usize {
+        self.running()
+            .map_or(3, |freezable| freezable.state_count())
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self.running() {
+            Some(freezable) => freezable.fmt_state(f),
+            None => f.write_str(self.state_name()),
+        }
+    }
+}
+
+impl<F: Freezable> JoinAll<F> {
+    /// the first one that is not finished yet (or the last one, when all of them are finished),
+    /// its state is the state of the join. `None` if there are no freezables
+    fn running(&self) -> Option<&F> {
+        self.freezables
+            .iter()
+            .find(|freezable| !freezable.is_finished())
+            .or(self.freezables.last())
+    }
+}
+
+/// see `select_all`
+pub struct SelectAll<F> {
+    freezables: Vec<F>,
+    finished: bool,
+}
+
+impl<F: Freezable> Freezable for SelectAll<F> {
+    type Output = (usize, F::Output);
+
+    fn unfreeze(&mut self) -> Result<FreezableState<(usize, F::Output)>, FreezableError> {
+        if self.finished {
+            return Err(FreezableError::AlreadyFinished);
+        }
+        for index in 0..self.freezables.len() {
+            if let FreezableState::Finished(output) = self.freezables[index].unfreeze()? {
+                self.finished = true;
+                // the ones that are not finished are cancelled, the finished ones would lose their state
+                self.freezables
+                    .iter_mut()
+                    .filter(|freezable| !freezable.is_finished())
+                    .for_each(|freezable| freezable.cancel());
+                return Ok(FreezableState::Finished((index, output)));
+            }
+        }
+        Ok(FreezableState::Frozen(None))
+    }
+
+    fn cancel(&mut self) {
+        if !self.finished {
+            self.freezables
+                .iter_mut()
+                .for_each(|freezable| freezable.cancel());
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        !self.finished
+            && self
+                .freezables
+                .iter()
+                .any(|freezable| freezable.is_cancelled())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn state_name(&self) -> &'static str {
+        self.running().state_name()
+    }
+
+    fn state_index(&self) -> usize {
+        self.running().state_index()
+    }
+
+    fn state_count(&self) -> usize {
+        self.running().state_count()
+    }
+
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.running().fmt_state(f)
+    }
+}
+
+impl<F: Freezable> SelectAll<F> {
+    /// the one that is finished first, or the first one while none of them is finished,
+    /// its state is the state of the select
+    fn running(&self) -> &F {
+        self.freezables
+            .iter()
+            .find(|freezable| freezable.is_finished())
+            .unwrap_or(&self.freezables[0])
+    }
+}
+
+/// `join` for two or more freezables, of any type: `join!(a, b, c)` finishes with `(a, b, c)`
+///
+/// the freezables are joined in pairs -> `join(a, join(b, c))`, and the output is flattened
+#[macro_export]
+macro_rules! join {
+    ($first: expr, $($rest: expr),+ $(,)?) => {
+        $crate::__join_named!(
+            [$first, $($rest),+]
+            []
+            [output0 output1 output2 output3 output4 output5 output6 output7 output8 output9 output10 output11]
+        )
+    };
+}
+
+/// gives a name to the output of every freezable, for the pattern that flattens the output of `join!`
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __join_named {
+    ([$head: expr $(, $rest: expr)*] [$($named: tt)*] [$name: ident $($names: ident)*]) => {
+        $crate::__join_named!([$($rest),*] [$($named)* ($head, $name)] [$($names)*])
+    };
+    ([$head: expr $(, $rest: expr)*] [$($named: tt)*] []) => {
+        compile_error!("`join!` takes at most 12 freezables, use `join_all` for more of them")
+    };
+    ([] [$(($freezable: expr, $name: ident))+] [$($unused: ident)*]) => {
+        $crate::FreezableExt::map(
+            $crate::__join_nested!($($freezable),+),
+            |$crate::__join_pattern!($($name)+)| ($($name,)+),
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __join_nested {
+    ($last: expr) => {
+        $last
+    };
+    ($first: expr, $($rest: expr),+) => {
+        $crate::join($first, $crate::__join_nested!($($rest),+))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __join_pattern {
+    ($last: ident) => {
+        $last
+    };
+    ($first: ident $($rest: ident)+) => {
+        ($first, $crate::__join_pattern!($($rest)+))
+    };
+}
+
+/// `select` for two or more freezables with the same output, of any type:
+/// `select!(a, b, c)` finishes with the index of the first one that is finished, and its output
+///
+/// the freezables are selected in pairs -> `select(a, select(b, c))`
+#[macro_export]
+macro_rules! select {
+    ($first: expr, $second: expr $(,)?) => {
+        $crate::FreezableExt::map($crate::select($first, $second), |selected| match selected {
+            $crate::Selected::First(output) => (0usize, output),
+            $crate::Selected::Second(output) => (1usize, output),
+        })
+    };
+    ($first: expr, $($rest: expr),+ $(,)?) => {
+        $crate::FreezableExt::map(
+            $crate::select($first, $crate::select!($($rest),+)),
+            |selected| match selected {
+                $crate::Selected::First(output) => (0usize, output),
+                $crate::Selected::Second((index, output)) => (index + 1, output),
+            },
+        )
+    };
+}
+
+#[test]
+fn join_test() {
+    use crate::{FreezableComplex, FreezableGenerator4};
+
+    let mut both = join(FreezableGenerator4::start(1), FreezableComplex::start(5));
+    for _ in 0..3 {
+        assert_eq!(both.unfreeze(), Ok(FreezableState::Frozen(None)));
+    }
+    // the first one is finished, and waits for the other one
+    assert!(!both.is_finished());
+    assert_eq!(
+        both.unfreeze(),
+        Ok(FreezableState::Finished((4, "24 a rando".to_string())))
+    );
+    assert!(both.is_finished());
+    assert_eq!(both.unfreeze(), Err(FreezableError::AlreadyFinished));
+}
+
+#[test]
+fn select_test() {
+    use crate::{FreezableComplex, FreezableGenerator4};
+
+    let mut first = select(
+        FreezableGenerator4::start(1),
+        FreezableGenerator4::start(10),
+    );
+    for _ in 0..3 {
+        assert_eq!(first.unfreeze(), Ok(FreezableState::Frozen(None)));
+    }
+    // both of them finish in the same step, the first one wins
+    assert_eq!(
+        first.unfreeze(),
+        Ok(FreezableState::Finished(Selected::First(4)))
+    );
+    assert!(first.is_finished());
+    assert!(!first.is_cancelled());
+    assert!(first.second.is_cancelled());
+    assert_eq!(first.unfreeze(), Err(FreezableError::AlreadyFinished));
+
+    let mut second = select(FreezableComplex::start(5), FreezableGenerator4::start(1));
+    let _ = second.unfreeze();
+    assert_eq!(
+        format!("{:?}", second.debug_state()),
+        "Chunk1 { current_num: 5 }"
+    );
+    second.cancel();
+    assert!(second.is_cancelled());
+    assert_eq!(second.unfreeze(), Err(FreezableError::Cancelled));
+}
+
+#[test]
+fn join_all_test() {
+    use crate::FreezableGenerator4;
+
+    let tasks = vec![
+        FreezableGenerator4::start(1),
+        FreezableGenerator4::start(10),
+        FreezableGenerator4::start(20),
+    ];
+    let mut all = join_all(tasks);
+    for _ in 0..3 {
+        assert_eq!(all.unfreeze(), Ok(FreezableState::Frozen(None)));
+    }
+    assert_eq!(format!("{:?}", all.debug_state()), "Chunk3 { next: 3 }");
+    assert_eq!(
+        all.unfreeze(),
+        Ok(FreezableState::Finished(vec![4, 13, 23]))
+    );
+    assert!(all.is_finished());
+
+    let mut none = join_all(Vec::<FreezableGenerator4>::new());
+    assert_eq!(none.state_name(), "Frozen");
+    assert_eq!(none.unfreeze(), Ok(FreezableState::Finished(vec![])));
+    assert_eq!(none.state_name(), "Finished");
+}
+
+#[test]
+fn select_all_test() {
+    use crate::FreezableGenerator4;
+
+    let mut tasks = vec![
+        FreezableGenerator4::start(1),
+        FreezableGenerator4::start(10),
+    ];
+    // the second one is one step ahead
+    let _ = tasks[1].unfreeze();
+    let mut any = select_all(tasks).expect("there are freezables to select from");
+    for _ in 0..2 {
+        assert_eq!(any.unfreeze(), Ok(FreezableState::Frozen(None)));
+    }
+    // the state of the first one, while none of them is finished
+    assert_eq!(format!("{:?}", any.debug_state()), "Chunk2 { next: 2 }");
+    assert_eq!(any.unfreeze(), Ok(FreezableState::Finished((1, 13))));
+    // the state of the one that is finished
+    assert_eq!(any.state_name(), "Finished");
+    assert_eq!(any.state_index(), 4);
+    assert!(any.freezables[0].is_cancelled());
+    assert!(any.freezables[1].is_finished());
+
+    assert!(select_all(Vec::<FreezableGenerator4>::new()).is_none());
+}
+
+#[test]
+fn variadic_test() {
+    use crate::{FreezableComplex, FreezableExt, FreezableGenerator4, FreezableIter};
+
+    let mut all = FreezableIter::new(join!(
+        FreezableGenerator4::start(1),
+        FreezableComplex::start(5),
+        FreezableGenerator4::start(10),
+    ));
+    assert_eq!(all.next(), None);
+    assert_eq!(all.into_output(), Some((4, "24 a rando".to_string(), 13)));
+
+    let mut any = FreezableIter::new(select!(
+        FreezableGenerator4::start(1),
+        FreezableGenerator4::start(10).take_states(2),
+        FreezableGenerator4::start(20),
+    ));
+    let _ = any.next();
+    // the second one is cancelled on the third step, and so is the select
+    assert!(any.freezable().is_cancelled());
+
+    let mut any = FreezableIter::new(select!(
+        FreezableComplex::start(5).map(|output| output.len() as u8),
+        FreezableGenerator4::start(10),
+    ));
+    // both of them finish in the same step, the first one wins
+    assert_eq!(any.next(), None);
+    assert_eq!(any.into_output(), Some((0, 10)));
+}
@@ -7,12 +7,20 @@
 //! It will be cumbersome and not intuitive to write the desugared version of a such `freezable` function
 //! The aim of this library is to uncover the secrets and underlying code of such concepts :)
 
+mod ext;
 mod freezable_complex;
 mod freezable_generator_4;
+mod generator;
+mod iter;
+mod join;
 
+pub use ext::{Chain, FreezableExt, Map, TakeStates, Then, Zip};
 pub use freezable_complex::FreezableComplex;
 pub use freezable_generator_4::FreezableGenerator4;
-use std::fmt::{Debug, Display};
+pub use generator::{Generator, GeneratorState};
+pub use iter::FreezableIter;
+pub use join::{join, join_all, select, select_all, Join, JoinAll, Select, SelectAll, Selected};
+use std::fmt::{Debug, Display, Formatter};
 
 /// Freezable trait
 ///
@@ -38,6 +46,189 @@ pub trait Freezable {
     fn is_cancelled(&self) -> bool;
 
     fn is_finished(&self) -> bool;
+
+    /// the name of the current state, for logging and debugging
+    ///
+    /// the `#[freezable]` functions return the name of the variant they are in -> `"Chunk2"`,
+    /// or the label of the `freeze!(@AwaitingHandshake)` they are frozen at
+    fn state_name(&self) -> &'static str {
+        if self.is_finished() {
+            "Finished"
+        } else if self.is_cancelled() {
+            "Cancelled"
+        } else {
+            "Frozen"
+        }
+    }
+
+    /// the index of the current state, in the order of the states:
+    /// the chunks of the function first, then `Finished` and `Cancelled`
+    ///
+    /// without the chunks, the states are `Frozen`, `Finished` and `Cancelled`
+    fn state_index(&self) -> usize {
+        if self.is_finished() {
+            1
+        } else if self.is_cancelled() {
+            2
+        } else {
+            0
+        }
+    }
+
+    /// the number of the states, including `Finished` and `Cancelled`
+    fn state_count(&self) -> usize {
+        3
+    }
+
+    /// writes the current state along with the variables that are stored in it -> `Chunk2 { num1: 4, num2: 2 }`
+    ///
+    /// the `#[freezable]` functions write the variables whose types implement `Debug`, the others are written as `_`.
+    /// Use `debug_state` to get something that can be printed with `{:?}`
+    fn fmt_state(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str(self.state_name())
+    }
+
+    /// the current state as something printable -> `println!("{:?}", task.debug_state())`,
+    /// or `DebugState(&*task)` for a `dyn Freezable`
+    fn debug_state(&self) -> DebugState<'_, Self>
+    where
+        Self: Sized,
+    {
+        DebugState(self)
+    }
+
+    /// an `Iterator` over the partial results (`Frozen(Some(value))`), the output is kept when it is finished
+    /// -> `task.into_iter_partials().map(...)`
+    fn into_iter_partials(self) -> FreezableIter<Self>
+    where
+        Self: Sized,
+    {
+        FreezableIter::new(self)
+    }
+}
+
+/// prints the current state of a Freezable with `{:?}`, see `Freezable::fmt_state`
+pub struct DebugState<'a, F: Freezable + ?Sized>(pub &'a F);
+
+impl<F: Freezable + ?Sized> Debug for DebugState<'_, F> {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.0.fmt_state(f)
+    }
+}
+
+/// used by the code that is generated by `#[freezable]`
+#[doc(hidden)]
+pub mod __private {
+    use std::convert::Infallible;
+    use std::fmt::Debug;
+    use std::ops::ControlFlow;
+
+    /// a variable that is stored in a state. It is printed with `Debug` if its type implements it,
+    /// otherwise as `_`: `(&Captured(&value)).as_debug()` picks `DebugCaptured` over `NotDebugCaptured`
+    /// if it can, since it needs one less reference
+    pub struct Captured<'a, T: ?Sized>(pub &'a T);
+
+    pub trait DebugCaptured {
+        fn as_debug(&self) -> &dyn Debug;
+    }
+
+    impl<T: Debug + ?Sized> DebugCaptured for Captured<'_, T> {
+        fn as_debug(&self) -> &dyn Debug {
+            &self.0
+        }
+    }
+
+    pub trait NotDebugCaptured {
+        fn as_debug(&self) -> &dyn Debug;
+    }
+
+    impl<T: ?Sized> NotDebugCaptured for &Captured<'_, T> {
+        fn as_debug(&self) -> &dyn Debug {
+            &Hidden
+        }
+    }
+
+    /// the `?` operator of the freezable functions, like the unstable `std::ops::Try`:
+    /// `branch` gives the value to continue with, or the residual (the error or the `None`) to finish with
+    #[diagnostic::on_unimplemented(
+        message = "`?` can only be used on a `Result` or an `Option` in a freezable function, not on `{Self}`"
+    )]
+    pub trait Try {
+        type Value;
+        type Residual;
+
+        fn branch(self) -> ControlFlow<Self::Residual, Self::Value>;
+    }
+
+    /// turns the residual of `?` into the return value of the function, the error is converted with `From`
+    #[diagnostic::on_unimplemented(
+        message = "`?` can't finish a freezable function that returns `{Self}` with `{R}`",
+        label = "only `Result` (in a function that returns a `Result`) and `Option` (in a function \
+                 that returns an `Option`) are supported"
+    )]
+    pub trait FromResidual<R> {
+        fn from_residual(residual: R) -> Self;
+    }
+
+    impl<T, E> Try for Result<T, E> {
+        type Value = T;
+        type Residual = Result<Infallible, E>;
+
+        fn branch(self) -> ControlFlow<Result<Infallible, E>, T> {
+            match self {
+                Ok(value) => ControlFlow::Continue(value),
+                Err(error) => ControlFlow::Break(Err(error)),
+            }
+        }
+    }
+
+    impl<T, E, F: From<E>> FromResidual<Result<Infallible, E>> for Result<T, F> {
+        fn from_residual(residual: Result<Infallible, E>) -> Self {
+            match residual {
+                Err(error) => Err(From::from(error)),
+                Ok(never) => match never {},
+            }
+        }
+    }
+
+    impl<T> Try for Option<T> {
+        type Value = T;
+        type Residual = Option<Infallible>;
+
+        fn branch(self) -> ControlFlow<Option<Infallible>, T> {
+            match self {
+                Some(value) => ControlFlow::Continue(value),
+                None => ControlFlow::Break(None),
+            }
+        }
+    }
+
+    impl<T> FromResidual<Option<Infallible>> for Option<T> {
+        fn from_residual(_: Option<Infallible>) -> Self {
+            None
+        }
+    }
+
+    struct Hidden;
+
+    impl Debug for Hidden {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("_")
+        }
+    }
+}
+
+/// Freezable, that receives a value every time it is unfrozen
+///
+/// imitates a coroutine: `let data: R = freeze!(x);` freezes the function, and when it is unfrozen with
+/// `unfreeze_with(input)`, `data` will be the `input`. This is how a runtime could hand the result of an
+/// I/O operation back into the task that was waiting for it.
+///
+/// The input is dropped if the function doesn't wait for it (for example, in the first `unfreeze_with` call).
+/// Unfreezing a function that waits for an input with `unfreeze` returns `FreezableError::MissingInput`
+pub trait FreezableWith<R>: Freezable {
+    /// same as `unfreeze`, but gives the `input` to the `freeze!()` the function is frozen at
+    fn unfreeze_with(&mut self, input: R) -> Result<FreezableState<Self::Output>, FreezableError>;
 }
 
 /// States for our Freezable
@@ -59,6 +250,7 @@ pub enum FreezableState<T> {
 pub enum FreezableError {
     Cancelled,
     AlreadyFinished,
+    MissingInput,
 }
 
 impl Display for FreezableError {
@@ -66,6 +258,12 @@ impl Display for FreezableError {
         match self {
             FreezableError::Cancelled => write!(f, "The function is cancelled!"),
             FreezableError::AlreadyFinished => write!(f, "The function is already finished!"),
+            FreezableError::MissingInput => {
+                write!(
+                    f,
+                    "The function is waiting for an input, use `unfreeze_with`!"
+                )
+            }
         }
     }
 }
@@ -76,8 +274,30 @@ impl Display for FreezableError {
 /// we will be using this empty macro for the same purpose
 /// check out the `freezable_complex` and `freezable_generator` for
 /// the envisaged usages of this function
+///
+/// the state the function is frozen at can be named with a label: `freeze!(@AwaitingHandshake)`,
+/// or `freeze!(@AwaitingHandshake, next)` to return a partial result as well
 #[macro_export]
 macro_rules! freeze {
     () => {};
     ($a: expr) => {};
+    (@$label: ident) => {};
+    (@$label: ident, $a: expr) => {};
+}
+
+/// empty macro for yielding an item from a `#[generator]` function
+///
+/// `yield_!(x)` freezes the function, and `x` is the next item of the iterator
+#[macro_export]
+macro_rules! yield_ {
+    ($a: expr) => {};
+}
+
+/// empty macro for awaiting another freezable inside of a freezable function
+///
+/// `let output: u8 = await_freezable!(other::start(5));` runs `other` until it is finished,
+/// our function freezes every time `other` freezes. This is what `.await` does for the futures
+#[macro_export]
+macro_rules! await_freezable {
+    ($a: expr) => {};
 }
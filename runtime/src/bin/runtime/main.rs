@@ -2,9 +2,6 @@ use runtime::runtime;
 
 use freezable::{Freezable, FreezableGenerator4};
 
-#[cfg(not(feature = "printable_states"))]
-use freezable::{FreezableError, FreezableState};
-#[cfg(not(feature = "printable_states"))]
 use freezable_macro::freezable;
 
 // LOOK BELOW (main_custom function) FOR THE CUSTOM EXAMPLE THAT YOU CAN USE YOUR OWN FUNCTION!
@@ -48,7 +45,6 @@ fn main() {
 }
 
 // if you want to use your custom function, use this function instead of the actual `main`
-#[cfg(not(feature = "printable_states"))]
 #[allow(dead_code)]
 fn main_custom() {
     println!("Running your custom functions!");
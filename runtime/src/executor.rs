@@ -1,6 +1,3 @@
-#[cfg(feature = "printable_states")]
-use freezable::FreezableGenerator4;
-
 use freezable::Freezable;
 use rand::Rng;
 use std::collections::HashMap;
@@ -13,6 +10,8 @@ use std::sync::mpsc::{Receiver, Sender};
 /// When these resources are ready, calls `unfreeze` on the relevant tasks
 ///
 /// You can compare this Executor to the `select` of `tokio` or `futures` if you like.
+/// The tasks can be composed with `freezable::join` and `freezable::select` as well,
+/// they are driven by this Executor like any other task
 /// This Executor's aim is prove that concurrently running some interruptible tasks
 /// in a single thread is possible.
 ///
@@ -21,8 +20,7 @@ use std::sync::mpsc::{Receiver, Sender};
 ///
 /// This executor does not do any error handling for simplicity. It just ignores the errors.
 pub fn start_executor(
-    #[cfg(not(feature = "printable_states"))] tasks: &mut [impl Freezable],
-    #[cfg(feature = "printable_states")] tasks: &mut [FreezableGenerator4],
+    tasks: &mut [impl Freezable],
     event_sender: Sender<u8>,
     awake_signal_recv: Receiver<u8>,
 ) {
@@ -116,14 +114,19 @@ pub fn start_executor(
     }
 }
 
+/// prints the state the task is in, and the variables stored in it -> `frozen in state 1 of 6: Chunk1 { next: 10 }`
 #[cfg(feature = "printable_states")]
-fn print_state(task: &FreezableGenerator4) {
-    match task {
-        FreezableGenerator4::Chunk0(val) => println!("frozen in state: 0, with value: {val}"),
-        FreezableGenerator4::Chunk1(val) => println!("frozen in state: 1, with value: {val}"),
-        FreezableGenerator4::Chunk2(val) => println!("frozen in state: 2, with value: {val}"),
-        FreezableGenerator4::Chunk3(val) => println!("frozen in state: 3, with value: {val}"),
-        FreezableGenerator4::Finished => println!("Finished!"),
-        FreezableGenerator4::Cancelled => println!("Cancelled"),
+fn print_state(task: &impl Freezable) {
+    if task.is_finished() {
+        println!("Finished!");
+    } else if task.is_cancelled() {
+        println!("Cancelled");
+    } else {
+        println!(
+            "frozen in state {} of {}: {:?}",
+            task.state_index(),
+            task.state_count(),
+            task.debug_state()
+        );
     }
 }
@@ -6,9 +6,6 @@ pub use executor::start_executor;
 pub use os_simulation::simulate_os;
 pub use reactor::start_reactor;
 
-#[cfg(feature = "printable_states")]
-use freezable::FreezableGenerator4;
-
 use freezable::Freezable;
 use std::sync::mpsc;
 use std::thread;
@@ -19,10 +16,7 @@ use std::thread;
 ///
 /// you can create your custom `freezable` tasks via using the `freezable-macro`,
 /// and supply them to `runtime` via the `tasks` argument, and have the most fun time of your life!
-pub fn runtime(
-    #[cfg(not(feature = "printable_states"))] tasks: &mut [impl Freezable],
-    #[cfg(feature = "printable_states")] tasks: &mut [FreezableGenerator4],
-) {
+pub fn runtime(tasks: &mut [impl Freezable]) {
     let (subscription_sender, subscription_recv) = mpsc::channel();
     let (notification_sender, notification_recv) = mpsc::channel();
     let (event_sender, event_recv) = mpsc::channel();
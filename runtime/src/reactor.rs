@@ -20,7 +20,6 @@ use std::{thread, time};
 /// In practice, the `Reactor` will listen to the OS for the notifications,
 /// and then call the `Waker.wake()` on the relevant tasks, making them ready to be polled for
 /// the `Executor`. Refer to the root README for further details
-
 pub fn start_reactor(
     event_recv: Receiver<u8>,
     awake_signal_sender: Sender<u8>,
@@ -1,7 +1,7 @@
 use runtime::{runtime, simulate_os, start_executor, start_reactor};
 
+use freezable::Freezable;
 use freezable::FreezableGenerator4;
-use freezable::{Freezable, FreezableError, FreezableState};
 use freezable_macro::freezable;
 use rand::Rng;
 use std::collections::HashSet;
@@ -124,3 +124,43 @@ fn runtime_with_macro() {
 
     assert!(tasks.iter().all(|task| task.is_finished()));
 }
+
+#[test]
+fn runtime_with_async_fn() {
+    #[freezable]
+    fn read_value(resource: u8) -> u8 {
+        freeze!(); // waiting for the resource
+        resource * 2
+    }
+
+    #[freezable]
+    async fn sum_of_reads(first: u8, second: u8) -> u8 {
+        let a: u8 = read_value::start(first).await;
+        let b: u8 = read_value::start(second).await;
+        a + b
+    }
+
+    let mut tasks = vec![sum_of_reads::start(1, 2), sum_of_reads::start(3, 4)];
+
+    runtime(&mut tasks);
+
+    assert!(tasks.iter().all(|task| task.is_finished()));
+}
+
+#[test]
+fn runtime_with_join_and_select() {
+    let joined = freezable::join_all(vec![
+        FreezableGenerator4::start(1),
+        FreezableGenerator4::start(10),
+    ]);
+    let selected = freezable::select_all(vec![
+        FreezableGenerator4::start(20),
+        FreezableGenerator4::start(30),
+    ])
+    .expect("there are tasks to select from");
+    let mut tasks = vec![freezable::join(joined, selected)];
+
+    runtime(&mut tasks);
+
+    assert!(tasks.iter().all(|task| task.is_finished()));
+}